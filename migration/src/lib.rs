@@ -6,6 +6,25 @@ mod m20241216_055637_create_permission_table;
 mod m20241216_092524_create_role_table;
 mod m20241216_095114_create_user_role_table;
 mod m20241217_163324_create_user_permission_table;
+mod m20261017_090000_create_revoked_token_table;
+mod m20261017_093000_create_token_family_table;
+mod m20261017_100000_add_email_verified_at_to_user_table;
+mod m20261017_110000_create_password_reset_token_table;
+mod m20261017_120000_add_totp_to_user_table;
+mod m20261017_120100_create_recovery_code_table;
+mod m20261017_130000_add_login_lockout_to_user_table;
+mod m20261017_140000_add_session_details_to_token_family_table;
+mod m20261017_150000_create_api_key_table;
+mod m20261017_150100_create_api_key_permission_table;
+mod m20261017_160000_create_user_identity_table;
+mod m20261017_160100_create_oauth_state_table;
+mod m20261017_170000_add_token_version_to_user_table;
+mod m20261017_180000_create_password_history_table;
+mod m20261017_190000_add_pending_email_to_user_table;
+mod m20261017_200000_add_case_insensitive_user_identifier_indexes;
+mod m20261017_210000_create_role_permission_table;
+mod m20261017_220000_add_parent_id_to_role_table;
+mod m20261017_230000_add_deactivated_at_to_user_table;
 
 pub struct Migrator;
 
@@ -19,6 +38,25 @@ impl MigratorTrait for Migrator {
             Box::new(m20241216_092524_create_role_table::Migration),
             Box::new(m20241216_095114_create_user_role_table::Migration),
             Box::new(m20241217_163324_create_user_permission_table::Migration),
+            Box::new(m20261017_090000_create_revoked_token_table::Migration),
+            Box::new(m20261017_093000_create_token_family_table::Migration),
+            Box::new(m20261017_100000_add_email_verified_at_to_user_table::Migration),
+            Box::new(m20261017_110000_create_password_reset_token_table::Migration),
+            Box::new(m20261017_120000_add_totp_to_user_table::Migration),
+            Box::new(m20261017_120100_create_recovery_code_table::Migration),
+            Box::new(m20261017_130000_add_login_lockout_to_user_table::Migration),
+            Box::new(m20261017_140000_add_session_details_to_token_family_table::Migration),
+            Box::new(m20261017_150000_create_api_key_table::Migration),
+            Box::new(m20261017_150100_create_api_key_permission_table::Migration),
+            Box::new(m20261017_160000_create_user_identity_table::Migration),
+            Box::new(m20261017_160100_create_oauth_state_table::Migration),
+            Box::new(m20261017_170000_add_token_version_to_user_table::Migration),
+            Box::new(m20261017_180000_create_password_history_table::Migration),
+            Box::new(m20261017_190000_add_pending_email_to_user_table::Migration),
+            Box::new(m20261017_200000_add_case_insensitive_user_identifier_indexes::Migration),
+            Box::new(m20261017_210000_create_role_permission_table::Migration),
+            Box::new(m20261017_220000_add_parent_id_to_role_table::Migration),
+            Box::new(m20261017_230000_add_deactivated_at_to_user_table::Migration),
         ]
     }
 }
@@ -0,0 +1,51 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // `user_id` is intentionally not a foreign key: revocations must outlive a deleted user
+        // until the revoked tokens have expired.
+        manager
+            .create_table(
+                Table::create()
+                    .table(RevokedToken::Table)
+                    .if_not_exists()
+                    .col(pk_auto(RevokedToken::Id))
+                    .col(string_null(RevokedToken::Jti).unique_key())
+                    .col(integer(RevokedToken::UserId))
+                    .col(date_time(RevokedToken::ExpiresAt))
+                    .col(date_time(RevokedToken::DateCreated))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-revoked-token-user_id")
+                    .table(RevokedToken::Table)
+                    .col(RevokedToken::UserId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RevokedToken::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RevokedToken {
+    Table,
+    Id,
+    Jti,
+    UserId,
+    ExpiresAt,
+    DateCreated,
+}
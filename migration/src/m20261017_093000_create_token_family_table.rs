@@ -0,0 +1,53 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TokenFamily::Table)
+                    .if_not_exists()
+                    .col(pk_auto(TokenFamily::Id))
+                    .col(integer(TokenFamily::UserId))
+                    .col(string(TokenFamily::RefreshJti))
+                    .col(date_time_null(TokenFamily::RevokedAt))
+                    .col(date_time(TokenFamily::DateCreated))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-token-family-user_id")
+                            .from(TokenFamily::Table, TokenFamily::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TokenFamily::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum TokenFamily {
+    Table,
+    Id,
+    UserId,
+    RefreshJti,
+    RevokedAt,
+    DateCreated,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
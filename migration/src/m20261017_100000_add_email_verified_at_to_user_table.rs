@@ -0,0 +1,46 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(date_time_null(User::EmailVerifiedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        // accounts created before verification existed are treated as verified
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(User::Table)
+                    .value(User::EmailVerifiedAt, Expr::col(User::DateCreated))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::EmailVerifiedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    DateCreated,
+    EmailVerifiedAt,
+}
@@ -0,0 +1,55 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PasswordResetToken::Table)
+                    .if_not_exists()
+                    .col(pk_auto(PasswordResetToken::Id))
+                    .col(integer(PasswordResetToken::UserId))
+                    .col(string_uniq(PasswordResetToken::TokenHash))
+                    .col(date_time(PasswordResetToken::ExpiresAt))
+                    .col(date_time_null(PasswordResetToken::UsedAt))
+                    .col(date_time(PasswordResetToken::DateCreated))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-password-reset-token-user_id")
+                            .from(PasswordResetToken::Table, PasswordResetToken::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PasswordResetToken::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PasswordResetToken {
+    Table,
+    Id,
+    UserId,
+    TokenHash,
+    ExpiresAt,
+    UsedAt,
+    DateCreated,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
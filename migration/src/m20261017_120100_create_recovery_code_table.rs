@@ -0,0 +1,53 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RecoveryCode::Table)
+                    .if_not_exists()
+                    .col(pk_auto(RecoveryCode::Id))
+                    .col(integer(RecoveryCode::UserId))
+                    .col(string(RecoveryCode::CodeHash))
+                    .col(date_time_null(RecoveryCode::UsedAt))
+                    .col(date_time(RecoveryCode::DateCreated))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-recovery-code-user_id")
+                            .from(RecoveryCode::Table, RecoveryCode::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RecoveryCode::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RecoveryCode {
+    Table,
+    Id,
+    UserId,
+    CodeHash,
+    UsedAt,
+    DateCreated,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only supports adding one column per statement
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(integer(User::FailedLoginAttempts).default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(date_time_null(User::LastFailedLoginAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(date_time_null(User::LockedUntil))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            User::FailedLoginAttempts,
+            User::LastFailedLoginAt,
+            User::LockedUntil,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(User::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    FailedLoginAttempts,
+    LastFailedLoginAt,
+    LockedUntil,
+}
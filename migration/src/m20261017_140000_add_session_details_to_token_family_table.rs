@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only supports adding one column per statement
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TokenFamily::Table)
+                    .add_column(string_null(TokenFamily::UserAgent))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TokenFamily::Table)
+                    .add_column(string_null(TokenFamily::IpAddress))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TokenFamily::Table)
+                    .add_column(date_time_null(TokenFamily::LastRefreshedAt))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            TokenFamily::UserAgent,
+            TokenFamily::IpAddress,
+            TokenFamily::LastRefreshedAt,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(TokenFamily::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum TokenFamily {
+    Table,
+    UserAgent,
+    IpAddress,
+    LastRefreshedAt,
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiKey::Table)
+                    .if_not_exists()
+                    .col(pk_auto(ApiKey::Id))
+                    .col(integer(ApiKey::UserId))
+                    .col(string(ApiKey::Name))
+                    .col(string(ApiKey::KeyPrefix))
+                    .col(string_uniq(ApiKey::KeyHash))
+                    .col(date_time_null(ApiKey::ExpiresAt))
+                    .col(date_time_null(ApiKey::LastUsedAt))
+                    .col(date_time_null(ApiKey::RevokedAt))
+                    .col(date_time(ApiKey::DateCreated))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-api-key-user_id")
+                            .from(ApiKey::Table, ApiKey::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiKey::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApiKey {
+    Table,
+    Id,
+    UserId,
+    Name,
+    KeyPrefix,
+    KeyHash,
+    ExpiresAt,
+    LastUsedAt,
+    RevokedAt,
+    DateCreated,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
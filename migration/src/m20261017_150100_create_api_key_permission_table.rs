@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiKeyPermission::Table)
+                    .if_not_exists()
+                    .col(pk_auto(ApiKeyPermission::Id))
+                    .col(integer(ApiKeyPermission::ApiKeyId))
+                    .col(integer(ApiKeyPermission::PermissionId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-api-key-permission-api_key_id")
+                            .from(ApiKeyPermission::Table, ApiKeyPermission::ApiKeyId)
+                            .to(ApiKey::Table, ApiKey::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-api-key-permission-permission_id")
+                            .from(ApiKeyPermission::Table, ApiKeyPermission::PermissionId)
+                            .to(Permission::Table, Permission::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiKeyPermission::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApiKeyPermission {
+    Table,
+    Id,
+    ApiKeyId,
+    PermissionId,
+}
+
+#[derive(DeriveIden)]
+enum ApiKey {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Permission {
+    Table,
+    Id,
+}
@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserIdentity::Table)
+                    .if_not_exists()
+                    .col(pk_auto(UserIdentity::Id))
+                    .col(integer(UserIdentity::UserId))
+                    .col(string(UserIdentity::Provider))
+                    .col(string(UserIdentity::Subject))
+                    .col(string_null(UserIdentity::Email))
+                    .col(date_time_null(UserIdentity::LastLoginAt))
+                    .col(date_time(UserIdentity::DateCreated))
+                    .index(
+                        Index::create()
+                            .name("idx-user-identity-provider-subject")
+                            .col(UserIdentity::Provider)
+                            .col(UserIdentity::Subject)
+                            .unique(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-user-identity-user_id")
+                            .from(UserIdentity::Table, UserIdentity::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserIdentity::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserIdentity {
+    Table,
+    Id,
+    UserId,
+    Provider,
+    Subject,
+    Email,
+    LastLoginAt,
+    DateCreated,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
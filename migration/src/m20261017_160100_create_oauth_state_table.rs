@@ -0,0 +1,43 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OauthState::Table)
+                    .if_not_exists()
+                    .col(pk_auto(OauthState::Id))
+                    .col(string(OauthState::Provider))
+                    .col(string_uniq(OauthState::State))
+                    .col(string(OauthState::CodeVerifier))
+                    .col(string(OauthState::Nonce))
+                    .col(date_time(OauthState::ExpiresAt))
+                    .col(date_time(OauthState::DateCreated))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OauthState::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OauthState {
+    Table,
+    Id,
+    Provider,
+    State,
+    CodeVerifier,
+    Nonce,
+    ExpiresAt,
+    DateCreated,
+}
@@ -0,0 +1,51 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PasswordHistory::Table)
+                    .if_not_exists()
+                    .col(pk_auto(PasswordHistory::Id))
+                    .col(integer(PasswordHistory::UserId))
+                    .col(string(PasswordHistory::PasswordHash))
+                    .col(date_time(PasswordHistory::DateCreated))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-password-history-user_id")
+                            .from(PasswordHistory::Table, PasswordHistory::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PasswordHistory::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PasswordHistory {
+    Table,
+    Id,
+    UserId,
+    PasswordHash,
+    DateCreated,
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
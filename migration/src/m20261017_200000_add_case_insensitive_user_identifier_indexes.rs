@@ -0,0 +1,93 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, Statement},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Identifiers whose normalized forms belong to more than one user, with those users' ids.
+const CONFLICTS_QUERY: &str = r#"
+    SELECT 'email' AS kind, lower(trim(email)) AS identifier, group_concat(id, ', ') AS ids
+    FROM "user" GROUP BY lower(trim(email)) HAVING count(*) > 1
+    UNION ALL
+    SELECT 'username', lower(trim(username)), group_concat(id, ', ')
+    FROM "user" GROUP BY lower(trim(username)) HAVING count(*) > 1
+"#;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // conflicting accounts have to be merged or renamed by hand before the indexes fit
+        let conflicts = db
+            .query_all(Statement::from_string(
+                manager.get_database_backend(),
+                CONFLICTS_QUERY,
+            ))
+            .await?
+            .iter()
+            .map(|row| {
+                Ok(format!(
+                    "{} '{}' is shared by users {}",
+                    row.try_get::<String>("", "kind")?,
+                    row.try_get::<String>("", "identifier")?,
+                    row.try_get::<String>("", "ids")?,
+                ))
+            })
+            .collect::<Result<Vec<String>, DbErr>>()?;
+
+        if !conflicts.is_empty() {
+            return Err(DbErr::Migration(format!(
+                "Users differ only in the case of their identifiers: {}",
+                conflicts.join("; ")
+            )));
+        }
+
+        db.execute_unprepared(
+            r#"UPDATE "user" SET
+                email = lower(trim(email)),
+                username = trim(username),
+                pending_email = lower(trim(pending_email))"#,
+        )
+        .await?;
+
+        db.execute_unprepared(
+            r#"CREATE UNIQUE INDEX "idx-user-email-lower" ON "user" (lower(email))"#,
+        )
+        .await?;
+
+        db.execute_unprepared(
+            r#"CREATE UNIQUE INDEX "idx-user-username-lower" ON "user" (lower(username))"#,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-user-username-lower")
+                    .table(User::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-user-email-lower")
+                    .table(User::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+}
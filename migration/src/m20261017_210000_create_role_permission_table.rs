@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RolePermission::Table)
+                    .if_not_exists()
+                    .col(pk_auto(RolePermission::Id))
+                    .col(integer(RolePermission::RoleId))
+                    .col(integer(RolePermission::PermissionId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-role-permission-role_id")
+                            .from(RolePermission::Table, RolePermission::RoleId)
+                            .to(Role::Table, Role::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-role-permission-permission_id")
+                            .from(RolePermission::Table, RolePermission::PermissionId)
+                            .to(Permission::Table, Permission::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RolePermission::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RolePermission {
+    Table,
+    Id,
+    RoleId,
+    PermissionId,
+}
+
+#[derive(DeriveIden)]
+enum Role {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Permission {
+    Table,
+    Id,
+}
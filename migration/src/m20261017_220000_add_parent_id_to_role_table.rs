@@ -0,0 +1,37 @@
+use sea_orm_migration::{prelude::*, sea_orm::ConnectionTrait};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can only add a foreign key together with the column it is declared on
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"ALTER TABLE "role" ADD COLUMN "parent_id" integer NULL
+                    REFERENCES "role" ("id") ON DELETE SET NULL ON UPDATE CASCADE"#,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Role::Table)
+                    .drop_column(Role::ParentId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Role {
+    Table,
+    ParentId,
+}
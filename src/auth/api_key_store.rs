@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore as _};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait as _, ActiveValue::NotSet, ColumnTrait as _, Condition, ConnectionTrait,
+    DbErr, EntityTrait as _, QueryFilter as _, QueryOrder as _, Set, TransactionTrait as _,
+    sea_query::Expr,
+};
+use sha2::{Digest as _, Sha256};
+
+use crate::{
+    AppState,
+    auth::{deactivation::Deactivation, email_verification::EmailVerification},
+    error::AppError,
+    models::_entities::{api_key, api_key_permission, permission, user},
+};
+
+/// Prefix of every API key, so leaked keys are easy to recognise.
+const KEY_PREFIX: &str = "wab_";
+/// Characters of a key stored in clear text, to tell keys apart when listing them.
+const DISPLAY_PREFIX_LENGTH: usize = 12;
+
+/// API keys let services call the API as their owner, limited to the permissions the key is
+/// scoped to. Only the SHA-256 digest of a key is stored; the key itself is shown once, when it
+/// is created.
+pub struct ApiKeyStore;
+
+impl ApiKeyStore {
+    /// Creates a key for the user and returns it with its permissions and the secret key.
+    pub async fn create(
+        ctx: &Arc<AppState>,
+        user_id: i32,
+        name: &str,
+        expires_at: Option<NaiveDateTime>,
+        permission_code_names: &[String],
+    ) -> Result<(api_key::Model, Vec<permission::Model>, String), AppError> {
+        let now = Utc::now().naive_utc();
+
+        if expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(AppError::GenericError(
+                "The expiry must be in the future.".to_string(),
+            ));
+        }
+
+        let permissions = permission::Entity::find()
+            .filter(permission::Column::CodeName.is_in(permission_code_names))
+            .all(&ctx.db)
+            .await?;
+
+        let unknown_permissions: Vec<&String> = permission_code_names
+            .iter()
+            .filter(|code_name| {
+                !permissions
+                    .iter()
+                    .any(|permission| &permission.code_name == *code_name)
+            })
+            .collect();
+
+        if !unknown_permissions.is_empty() {
+            return Err(AppError::GenericError(format!(
+                "Unknown permissions: {unknown_permissions:?}"
+            )));
+        }
+
+        let mut secret_bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut secret_bytes);
+        let secret = format!("{KEY_PREFIX}{}", hex::encode(secret_bytes));
+
+        let txn = ctx.db.begin().await?;
+
+        let api_key = api_key::ActiveModel {
+            id: NotSet,
+            user_id: Set(user_id),
+            name: Set(name.to_string()),
+            key_prefix: Set(secret[..DISPLAY_PREFIX_LENGTH].to_string()),
+            key_hash: Set(hash_key(&secret)),
+            expires_at: Set(expires_at),
+            last_used_at: Set(None),
+            revoked_at: Set(None),
+            date_created: Set(now),
+        }
+        .insert(&txn)
+        .await?;
+
+        if !permissions.is_empty() {
+            api_key_permission::Entity::insert_many(permissions.iter().map(|permission| {
+                api_key_permission::ActiveModel {
+                    id: NotSet,
+                    api_key_id: Set(api_key.id),
+                    permission_id: Set(permission.id),
+                }
+            }))
+            .exec(&txn)
+            .await?;
+        }
+
+        txn.commit().await?;
+
+        Ok((api_key, permissions, secret))
+    }
+
+    pub async fn list<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+    ) -> Result<Vec<(api_key::Model, Vec<permission::Model>)>, AppError> {
+        let api_keys = api_key::Entity::find()
+            .filter(api_key::Column::UserId.eq(user_id))
+            .order_by_desc(api_key::Column::DateCreated)
+            .find_with_related(permission::Entity)
+            .all(db)
+            .await?;
+
+        Ok(api_keys)
+    }
+
+    pub async fn revoke<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        api_key_id: i32,
+    ) -> Result<(), AppError> {
+        let res = api_key::Entity::update_many()
+            .col_expr(
+                api_key::Column::RevokedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(api_key::Column::Id.eq(api_key_id))
+            .filter(api_key::Column::UserId.eq(user_id))
+            .filter(api_key::Column::RevokedAt.is_null())
+            .exec(db)
+            .await?;
+
+        if res.rows_affected == 0 {
+            return Err(DbErr::RecordNotFound("API key not found.".to_string()).into());
+        }
+
+        Ok(())
+    }
+
+    /// Resolves a key to its owner and records its use.
+    pub async fn authenticate(
+        ctx: &Arc<AppState>,
+        secret: &str,
+    ) -> Result<(user::Model, api_key::Model), AppError> {
+        let now = Utc::now().naive_utc();
+
+        let api_key = api_key::Entity::find()
+            .filter(api_key::Column::KeyHash.eq(hash_key(secret)))
+            .filter(api_key::Column::RevokedAt.is_null())
+            .filter(
+                Condition::any()
+                    .add(api_key::Column::ExpiresAt.is_null())
+                    .add(api_key::Column::ExpiresAt.gt(now)),
+            )
+            .one(&ctx.db)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+
+        let user = user::Entity::find_by_id(api_key.user_id)
+            .one(&ctx.db)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+
+        Deactivation::ensure_active(&user)?;
+        EmailVerification::ensure_verified(&ctx.config, &user)?;
+
+        let mut active_api_key: api_key::ActiveModel = api_key.into();
+        active_api_key.last_used_at = Set(Some(now));
+        let api_key = active_api_key.update(&ctx.db).await?;
+
+        Ok((user, api_key))
+    }
+}
+
+fn hash_key(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
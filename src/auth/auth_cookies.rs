@@ -0,0 +1,215 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore as _};
+use axum::{
+    http::{HeaderMap, Method},
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use serde::Serialize;
+
+use crate::{
+    api_response::JsonResponse, auth::jwt::UserToken, configgg::AppConfig, error::AppError,
+};
+
+pub const ACCESS_TOKEN_COOKIE: &str = "access_token";
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+/// Readable by scripts, which echo it in the `X-CSRF-Token` header of unsafe requests.
+pub const CSRF_TOKEN_COOKIE: &str = "csrf_token";
+pub const CSRF_TOKEN_HEADER: &str = "x-csrf-token";
+/// State of an identity provider sign in, so only the browser that started it can complete it.
+pub const OAUTH_STATE_COOKIE: &str = "oauth_state";
+
+/// The refresh token is only sent to the endpoints that use it.
+const REFRESH_TOKEN_PATH: &str = "/api/auth";
+const OAUTH_STATE_PATH: &str = "/api/auth/oauth";
+
+#[derive(Debug, Serialize)]
+pub struct CookieSession {
+    pub csrf_token: String,
+}
+
+/// Cookie session mode, enabled with `auth_cookies`.
+///
+/// Tokens are set as HttpOnly cookies so browser scripts never see them. Cookies are sent with
+/// cross-site requests too, so requests authenticated by cookie with an unsafe method must
+/// carry the `csrf_token` cookie's value in the `X-CSRF-Token` header, which other sites can't
+/// read or set (double-submit cookie).
+pub struct AuthCookies;
+
+impl AuthCookies {
+    /// Responds with newly issued tokens, as cookies in cookie mode and in the body otherwise.
+    pub fn token_response(
+        app_config: &AppConfig,
+        jar: CookieJar,
+        user_token: UserToken,
+    ) -> Response {
+        // the jar can carry cookies the caller changed, like the expired sign in state
+        if !app_config.auth_cookies {
+            return (jar, JsonResponse::data(user_token, None)).into_response();
+        }
+
+        let csrf_token = random_token();
+
+        let mut csrf_cookie = build_cookie(
+            app_config,
+            CSRF_TOKEN_COOKIE,
+            csrf_token.clone(),
+            "/",
+            app_config.refresh_token_expiration_minutes,
+        );
+        csrf_cookie.set_http_only(false);
+
+        let mut jar = jar.add(csrf_cookie).add(build_cookie(
+            app_config,
+            ACCESS_TOKEN_COOKIE,
+            user_token.access_token,
+            "/",
+            app_config.access_token_expiration_minutes,
+        ));
+
+        if let Some(refresh_token) = user_token.refresh_token {
+            jar = jar.add(build_cookie(
+                app_config,
+                REFRESH_TOKEN_COOKIE,
+                refresh_token,
+                REFRESH_TOKEN_PATH,
+                app_config.refresh_token_expiration_minutes,
+            ));
+        }
+
+        (
+            jar,
+            JsonResponse::data(
+                CookieSession { csrf_token },
+                Some("Signed in successfully.".to_string()),
+            ),
+        )
+            .into_response()
+    }
+
+    /// Expires the session cookies.
+    pub fn clear(app_config: &AppConfig, jar: CookieJar) -> CookieJar {
+        [
+            (ACCESS_TOKEN_COOKIE, "/"),
+            (REFRESH_TOKEN_COOKIE, REFRESH_TOKEN_PATH),
+            (CSRF_TOKEN_COOKIE, "/"),
+        ]
+        .into_iter()
+        .fold(jar, |jar, (name, path)| {
+            jar.remove(build_cookie(app_config, name, String::new(), path, 0))
+        })
+    }
+
+    /// Binds the state of an identity provider sign in to the browser starting it, in either
+    /// session mode.
+    pub fn add_oauth_state(app_config: &AppConfig, jar: CookieJar, state: String) -> CookieJar {
+        let mut cookie = build_cookie(
+            app_config,
+            OAUTH_STATE_COOKIE,
+            state,
+            OAUTH_STATE_PATH,
+            app_config.oauth_state_expiration_minutes,
+        );
+        // the provider redirects back with a cross-site navigation, which Strict would not send
+        // the cookie with
+        cookie.set_same_site(SameSite::Lax);
+
+        jar.add(cookie)
+    }
+
+    /// Checks that the browser completing a sign in is the one that started it, and expires the
+    /// state cookie.
+    pub fn take_oauth_state(
+        app_config: &AppConfig,
+        jar: CookieJar,
+        state: &str,
+    ) -> Result<CookieJar, AppError> {
+        match jar.get(OAUTH_STATE_COOKIE).map(Cookie::value) {
+            Some(cookie_state) if constant_time_eq(cookie_state, state) => {
+                Ok(jar.remove(build_cookie(
+                    app_config,
+                    OAUTH_STATE_COOKIE,
+                    String::new(),
+                    OAUTH_STATE_PATH,
+                    0,
+                )))
+            }
+            _ => Err(AppError::InvalidToken),
+        }
+    }
+
+    /// Checks the double-submitted CSRF token of a request authenticated by cookie.
+    pub fn verify_csrf(
+        method: &Method,
+        headers: &HeaderMap,
+        jar: &CookieJar,
+    ) -> Result<(), AppError> {
+        if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+            return Ok(());
+        }
+
+        let header_token = headers
+            .get(CSRF_TOKEN_HEADER)
+            .and_then(|header| header.to_str().ok());
+        let cookie_token = jar.get(CSRF_TOKEN_COOKIE).map(Cookie::value);
+
+        match (header_token, cookie_token) {
+            (Some(header_token), Some(cookie_token))
+                if !cookie_token.is_empty() && constant_time_eq(header_token, cookie_token) =>
+            {
+                Ok(())
+            }
+            _ => Err(AppError::InvalidCsrfToken),
+        }
+    }
+}
+
+fn build_cookie(
+    app_config: &AppConfig,
+    name: &'static str,
+    value: String,
+    path: &'static str,
+    max_age_minutes: i64,
+) -> Cookie<'static> {
+    let same_site = match app_config
+        .auth_cookie_same_site
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "strict" => SameSite::Strict,
+        "none" => SameSite::None,
+        _ => SameSite::Lax,
+    };
+
+    let mut cookie = Cookie::build((name, value))
+        .http_only(true)
+        .secure(app_config.auth_cookie_secure)
+        .same_site(same_site)
+        .path(path)
+        .max_age(time::Duration::minutes(max_age_minutes))
+        .build();
+
+    if let Some(domain) = app_config
+        .auth_cookie_domain
+        .as_deref()
+        .filter(|domain| !domain.trim().is_empty())
+    {
+        cookie.set_domain(domain.to_string());
+    }
+
+    cookie
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |difference, (x, y)| difference | (x ^ y))
+            == 0
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    hex::encode(bytes)
+}
@@ -1,16 +1,41 @@
-use std::sync::Arc;
+use std::{ops::Deref, sync::Arc};
 
-use sea_orm::{ColumnTrait as _, ModelTrait as _, PaginatorTrait as _, QueryFilter as _};
+use sea_orm::{
+    ColumnTrait as _, Condition, EntityTrait as _, ModelTrait as _, QueryFilter as _,
+    QueryOrder as _, QuerySelect as _, sea_query::Query,
+};
 
 use crate::{
-    error::AppError,
-    models::_entities::{permission, role, user},
     AppState,
+    auth::{permission_cache::UserGrants, permission_grant, role_hierarchy::RoleHierarchy},
+    error::AppError,
+    models::_entities::{
+        api_key, permission, role, role_permission, user, user_permission, user_role,
+    },
 };
 
+/// The authenticated caller, either signed in with an access token or acting through one of
+/// the user's API keys.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user: user::Model,
+    pub api_key: Option<api_key::Model>,
+    /// The staff member behind the request when the user is being impersonated.
+    pub impersonator: Option<user::Model>,
+}
+
+impl Deref for AuthUser {
+    type Target = user::Model;
+
+    fn deref(&self) -> &Self::Target {
+        &self.user
+    }
+}
+
 pub struct AuthService;
 
 impl AuthService {
+    #[allow(dead_code)]
     pub async fn has_role(
         ctx: &Arc<AppState>,
         user: &user::Model,
@@ -20,38 +45,154 @@ impl AuthService {
             return Ok(());
         }
 
-        let count = user
-            .find_related(role::Entity)
-            .filter(role::Column::Name.contains(role))
-            .count(&ctx.db)
-            .await?;
+        let grants = Self::user_grants(ctx, user).await?;
 
-        if count == 0 {
+        if !grants.roles.iter().any(|name| name == role) {
             return Err(AppError::Unauthorized);
         }
 
         Ok(())
     }
 
+    /// Checks the permission with the given code name, which grants match as described in
+    /// `permission_grant::grants`.
+    ///
+    /// Users hold the permissions assigned to them directly and those of their roles, including
+    /// the ones their roles inherit as described in `RoleHierarchy`. Requests
+    /// made with an API key are limited to the permissions the key is scoped to, on top of the
+    /// permissions of its owner.
     pub async fn has_permission(
         ctx: &Arc<AppState>,
-        user: &user::Model,
+        auth_user: &AuthUser,
         permission: &str,
     ) -> Result<(), AppError> {
-        if user.is_superadmin {
+        if let Some(api_key) = &auth_user.api_key {
+            let scopes = api_key
+                .find_related(permission::Entity)
+                .filter(permission_grant::candidates(permission))
+                .all(&ctx.db)
+                .await?;
+
+            let scopes = scopes.iter().map(|scope| scope.code_name.as_str());
+
+            if !any_grants(scopes, permission) {
+                return Err(AppError::Forbidden);
+            }
+        }
+
+        if auth_user.is_superadmin {
             return Ok(());
         }
 
-        let count = user
-            .find_related(permission::Entity)
-            .filter(permission::Column::Name.contains(permission))
-            .count(&ctx.db)
-            .await?;
+        let grants = Self::user_grants(ctx, auth_user).await?;
 
-        if count == 0 {
+        if !any_grants(grants.permissions.iter().map(String::as_str), permission) {
             return Err(AppError::Forbidden);
         }
 
         Ok(())
     }
+
+    /// Permissions `has_permission` grants the user, every permission for superadmins.
+    pub async fn effective_permissions(
+        ctx: &Arc<AppState>,
+        user: &user::Model,
+    ) -> Result<Vec<permission::Model>, AppError> {
+        let permissions = if user.is_superadmin {
+            permission::Entity::find()
+                .order_by_asc(permission::Column::CodeName)
+                .all(&ctx.db)
+                .await?
+        } else {
+            let role_ids = role_ids(ctx, user.id).await?;
+
+            permission::Entity::find()
+                .filter(granted_to(user.id, &role_ids))
+                .order_by_asc(permission::Column::CodeName)
+                .all(&ctx.db)
+                .await?
+        };
+
+        Ok(permissions)
+    }
+
+    /// Code names of the user's permissions and names of their roles, served from
+    /// `AppState::permission_cache` when possible.
+    pub async fn user_grants(
+        ctx: &Arc<AppState>,
+        user: &user::Model,
+    ) -> Result<Arc<UserGrants>, AppError> {
+        if let Some(grants) = ctx.permission_cache.get(user.id) {
+            return Ok(grants);
+        }
+
+        let generation = ctx.permission_cache.generation();
+
+        let role_ids = role_ids(ctx, user.id).await?;
+
+        let permissions = permission::Entity::find()
+            .select_only()
+            .column(permission::Column::CodeName)
+            .filter(granted_to(user.id, &role_ids))
+            .into_tuple()
+            .all(&ctx.db)
+            .await?;
+
+        let roles = role::Entity::find()
+            .filter(role::Column::Id.is_in(role_ids))
+            .select_only()
+            .column(role::Column::Name)
+            .into_tuple()
+            .all(&ctx.db)
+            .await?;
+
+        let grants = Arc::new(UserGrants { permissions, roles });
+
+        ctx.permission_cache
+            .insert(user.id, grants.clone(), generation);
+
+        Ok(grants)
+    }
+}
+
+fn any_grants<'a>(granted: impl IntoIterator<Item = &'a str>, required: &str) -> bool {
+    granted
+        .into_iter()
+        .any(|granted| permission_grant::grants(granted, required))
+}
+
+/// Ids of the user's roles together with the ones they inherit from.
+async fn role_ids(ctx: &Arc<AppState>, user_id: i32) -> Result<Vec<i32>, AppError> {
+    let assigned: Vec<i32> = user_role::Entity::find()
+        .select_only()
+        .column(user_role::Column::RoleId)
+        .filter(user_role::Column::UserId.eq(user_id))
+        .into_tuple()
+        .all(&ctx.db)
+        .await?;
+
+    if assigned.is_empty() {
+        return Ok(assigned);
+    }
+
+    Ok(RoleHierarchy::load(&ctx.db).await?.inherited(assigned))
+}
+
+/// Matches the permissions granted to the user directly or to one of the given roles.
+fn granted_to(user_id: i32, role_ids: &[i32]) -> Condition {
+    let direct = Query::select()
+        .column(user_permission::Column::PermissionId)
+        .from(user_permission::Entity)
+        .and_where(user_permission::Column::UserId.eq(user_id))
+        .to_owned();
+
+    let through_roles = Query::select()
+        .column(role_permission::Column::PermissionId)
+        .from(role_permission::Entity)
+        .and_where(role_permission::Column::RoleId.is_in(role_ids.iter().copied()))
+        .to_owned();
+
+    Condition::any()
+        .add(permission::Column::Id.in_subquery(direct))
+        .add(permission::Column::Id.in_subquery(through_roles))
 }
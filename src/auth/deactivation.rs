@@ -0,0 +1,51 @@
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait as _, ConnectionTrait, Set, TransactionTrait};
+
+use crate::{AppState, auth::token_store::TokenStore, error::AppError, models::_entities::user};
+
+/// Suspends accounts without deleting them.
+///
+/// Deactivating a user ends all of their sessions, and until they are reactivated they can't
+/// sign in or use their tokens and API keys.
+pub struct Deactivation;
+
+impl Deactivation {
+    pub async fn deactivate(ctx: &AppState, user: user::Model) -> Result<user::Model, AppError> {
+        if user.deactivated_at.is_some() {
+            return Ok(user);
+        }
+
+        let txn = ctx.db.begin().await?;
+
+        let mut user: user::ActiveModel = user.into();
+        user.deactivated_at = Set(Some(Utc::now().naive_utc()));
+        let user = user.update(&txn).await?;
+
+        TokenStore::revoke_all_for_user(&txn, user.id).await?;
+
+        txn.commit().await?;
+
+        Ok(user)
+    }
+
+    pub async fn reactivate<C: ConnectionTrait>(
+        db: &C,
+        user: user::Model,
+    ) -> Result<user::Model, AppError> {
+        if user.deactivated_at.is_none() {
+            return Ok(user);
+        }
+
+        let mut user: user::ActiveModel = user.into();
+        user.deactivated_at = Set(None);
+
+        Ok(user.update(db).await?)
+    }
+
+    pub fn ensure_active(user: &user::Model) -> Result<(), AppError> {
+        match user.deactivated_at {
+            Some(_) => Err(AppError::AccountDeactivated),
+            None => Ok(()),
+        }
+    }
+}
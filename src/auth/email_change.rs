@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait as _, ConnectionTrait, EntityTrait as _, PaginatorTrait as _,
+    QueryFilter as _, Set, TransactionTrait as _,
+};
+
+use crate::{
+    AppState,
+    auth::{
+        identifier::Identifier,
+        jwt::{TokenClaims, TokenType},
+    },
+    error::AppError,
+    mails::auth_mails::{send_email_change_mail, send_email_change_notice_mail},
+    models::_entities::user,
+};
+
+/// Changes a user's email address once the new address is confirmed.
+///
+/// The requested address is kept in `pending_email` and a link to confirm it is sent there, while
+/// the current address is told about the request. The link's `email_change` token names the
+/// address it confirms, so it stops working once another change is requested.
+pub struct EmailChange;
+
+impl EmailChange {
+    /// Stores the requested address and emails the confirmation link and the notice.
+    pub async fn request(
+        ctx: &Arc<AppState>,
+        user: user::Model,
+        new_email: &str,
+    ) -> Result<user::Model, AppError> {
+        // the token must name the address exactly as it is stored in `pending_email`
+        let new_email = &Identifier::normalize_email(new_email);
+
+        Self::ensure_available(&ctx.db, new_email).await?;
+
+        let token_claims = TokenClaims::new(
+            &user.id.to_string(),
+            TokenType::EmailChange,
+            ctx.config.email_verification_expiration_minutes,
+        )
+        .with_email(new_email);
+
+        let token = ctx
+            .jwt_keys
+            .encode(&token_claims)
+            .map_err(AppError::GenericError)?;
+
+        let confirmation_url = format!(
+            "{}/api/auth/confirm-email-change?token={}",
+            ctx.config.app_url.trim_end_matches('/'),
+            token
+        );
+
+        let mut user: user::ActiveModel = user.into();
+        user.pending_email = Set(Some(new_email.to_string()));
+        let user = user.update(&ctx.db).await?;
+
+        let app_state = ctx.clone();
+        let current_email = user.email.clone();
+        let new_email = new_email.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = send_email_change_mail(
+                app_state.clone(),
+                "Confirm your new email address",
+                &new_email,
+                &confirmation_url,
+            ) {
+                tracing::error!("Failed to send email change confirmation: {}", e);
+            }
+
+            if let Err(e) = send_email_change_notice_mail(
+                app_state,
+                "Your email address is being changed",
+                &current_email,
+                &new_email,
+            ) {
+                tracing::error!("Failed to send email change notice: {}", e);
+            }
+        });
+
+        Ok(user)
+    }
+
+    /// Fails if the address already belongs to an account.
+    pub async fn ensure_available<C: ConnectionTrait>(db: &C, email: &str) -> Result<(), AppError> {
+        if email_taken(db, email).await? {
+            return Err(AppError::GenericError(
+                "A user with this email already exists.".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Swaps in the address the token confirms, unless another account took it meanwhile.
+    pub async fn confirm(ctx: &Arc<AppState>, token: &str) -> Result<user::Model, AppError> {
+        let token_claims = ctx.jwt_keys.decode(token, TokenType::EmailChange)?;
+
+        let txn = ctx.db.begin().await?;
+
+        let user = user::Entity::find_by_id(token_claims.user_id()?)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+
+        let Some(new_email) = token_claims
+            .email
+            .filter(|email| user.pending_email.as_ref() == Some(email))
+        else {
+            return Err(AppError::InvalidToken);
+        };
+
+        let taken = email_taken(&txn, &new_email).await?;
+
+        let mut user: user::ActiveModel = user.into();
+        user.pending_email = Set(None);
+
+        if taken {
+            user.update(&txn).await?;
+            txn.commit().await?;
+
+            return Err(AppError::GenericError(
+                "The email address has been taken by another account.".to_string(),
+            ));
+        }
+
+        user.email = Set(new_email);
+        user.email_verified_at = Set(Some(Utc::now().naive_utc()));
+        let user = user.update(&txn).await?;
+
+        txn.commit().await?;
+
+        Ok(user)
+    }
+}
+
+async fn email_taken<C: ConnectionTrait>(db: &C, email: &str) -> Result<bool, AppError> {
+    let count = user::Entity::find()
+        .filter(Identifier::email_eq(email))
+        .count(db)
+        .await?;
+
+    Ok(count > 0)
+}
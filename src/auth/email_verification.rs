@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait as _, ColumnTrait as _, EntityTrait as _, QueryFilter as _, Set};
+
+use crate::{
+    AppState,
+    auth::jwt::{TokenClaims, TokenType},
+    configgg::AppConfig,
+    error::AppError,
+    models::_entities::user,
+};
+
+/// Issues and redeems the signed, expiring links that confirm a user's email address.
+///
+/// The link carries an `email_verification` token whose subject is the address being verified,
+/// so a link stops working once the user's email changes.
+pub struct EmailVerification;
+
+impl EmailVerification {
+    pub fn verification_url(ctx: &Arc<AppState>, user: &user::Model) -> Result<String, AppError> {
+        let token_claims = TokenClaims::new(
+            &user.email,
+            TokenType::EmailVerification,
+            ctx.config.email_verification_expiration_minutes,
+        );
+
+        let token = ctx
+            .jwt_keys
+            .encode(&token_claims)
+            .map_err(AppError::GenericError)?;
+
+        Ok(format!(
+            "{}/api/auth/verify-email?token={}",
+            ctx.config.app_url.trim_end_matches('/'),
+            token
+        ))
+    }
+
+    /// Marks the email address of the token's subject as verified.
+    pub async fn verify(ctx: &Arc<AppState>, token: &str) -> Result<user::Model, AppError> {
+        let token_claims = ctx.jwt_keys.decode(token, TokenType::EmailVerification)?;
+
+        let user = user::Entity::find()
+            .filter(user::Column::Email.eq(&token_claims.sub))
+            .one(&ctx.db)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+
+        if user.email_verified_at.is_some() {
+            return Ok(user);
+        }
+
+        let mut user: user::ActiveModel = user.into();
+        user.email_verified_at = Set(Some(Utc::now().naive_utc()));
+
+        Ok(user.update(&ctx.db).await?)
+    }
+
+    /// Fails for unverified users when `require_email_verification` is enabled.
+    pub fn ensure_verified(app_config: &AppConfig, user: &user::Model) -> Result<(), AppError> {
+        if app_config.require_email_verification && user.email_verified_at.is_none() {
+            return Err(AppError::EmailNotVerified);
+        }
+
+        Ok(())
+    }
+}
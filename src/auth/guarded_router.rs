@@ -0,0 +1,207 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use axum::{
+    Router,
+    extract::Request,
+    handler::Handler,
+    response::{IntoResponse as _, Response},
+    routing::{self, MethodRouter},
+};
+
+use crate::{
+    AppState,
+    auth::auth_service::{AuthService, AuthUser},
+    error::AppError,
+};
+
+/// Router of the API resources. Handlers are only added to it together with the code name of
+/// the permission they require, so a route can't be left without one.
+#[derive(Default)]
+pub struct GuardedRouter(Router<Arc<AppState>>);
+
+impl GuardedRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(self, path: &str, method_router: GuardedMethodRouter) -> Self {
+        Self(self.0.route(path, method_router.0))
+    }
+
+    pub fn nest(self, path: &str, router: GuardedRouter) -> Self {
+        Self(self.0.nest(path, router.0))
+    }
+}
+
+impl From<GuardedRouter> for Router<Arc<AppState>> {
+    fn from(value: GuardedRouter) -> Self {
+        value.0
+    }
+}
+
+/// Handlers of a route by method, each with the permission it requires.
+pub struct GuardedMethodRouter(MethodRouter<Arc<AppState>>);
+
+impl GuardedMethodRouter {
+    pub fn post<H, T>(self, permission: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, Arc<AppState>>,
+        T: 'static,
+    {
+        Self(self.0.post(RequirePermission::new(permission, handler)))
+    }
+
+    pub fn put<H, T>(self, permission: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, Arc<AppState>>,
+        T: 'static,
+    {
+        Self(self.0.put(RequirePermission::new(permission, handler)))
+    }
+
+    pub fn delete<H, T>(self, permission: &'static str, handler: H) -> Self
+    where
+        H: Handler<T, Arc<AppState>>,
+        T: 'static,
+    {
+        Self(self.0.delete(RequirePermission::new(permission, handler)))
+    }
+}
+
+pub fn get<H, T>(permission: &'static str, handler: H) -> GuardedMethodRouter
+where
+    H: Handler<T, Arc<AppState>>,
+    T: 'static,
+{
+    GuardedMethodRouter(routing::get(RequirePermission::new(permission, handler)))
+}
+
+pub fn post<H, T>(permission: &'static str, handler: H) -> GuardedMethodRouter
+where
+    H: Handler<T, Arc<AppState>>,
+    T: 'static,
+{
+    GuardedMethodRouter(routing::post(RequirePermission::new(permission, handler)))
+}
+
+pub fn delete<H, T>(permission: &'static str, handler: H) -> GuardedMethodRouter
+where
+    H: Handler<T, Arc<AppState>>,
+    T: 'static,
+{
+    GuardedMethodRouter(routing::delete(RequirePermission::new(permission, handler)))
+}
+
+/// Runs the handler once `AuthService::has_permission` lets the caller through.
+#[derive(Clone)]
+struct RequirePermission<H> {
+    permission: &'static str,
+    handler: H,
+}
+
+impl<H> RequirePermission<H> {
+    fn new(permission: &'static str, handler: H) -> Self {
+        Self {
+            permission,
+            handler,
+        }
+    }
+}
+
+impl<H, T> Handler<T, Arc<AppState>> for RequirePermission<H>
+where
+    H: Handler<T, Arc<AppState>>,
+{
+    type Future = Pin<Box<dyn Future<Output = Response> + Send>>;
+
+    fn call(self, request: Request, state: Arc<AppState>) -> Self::Future {
+        Box::pin(async move {
+            // set by the auth guard, without it the caller can't hold any permission
+            let Some(auth_user) = request.extensions().get::<AuthUser>().cloned() else {
+                return AppError::Unauthorized.into_response();
+            };
+
+            if let Err(e) = AuthService::has_permission(&state, &auth_user, self.permission).await {
+                return e.into_response();
+            }
+
+            self.handler.call(request, state).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{self, StatusCode};
+    use dotenvy::dotenv;
+    use sea_orm::{
+        ActiveModelTrait as _, ActiveValue::NotSet, ColumnTrait as _, EntityTrait as _,
+        QueryFilter as _, Set,
+    };
+    use serde_json::Value;
+
+    use crate::{
+        models::_entities::user_permission,
+        routes::create_router,
+        test_utils::{
+            access_token, find_or_create_permission, find_or_create_user, request, test_state,
+        },
+    };
+
+    async fn get_status(app: &axum::Router, uri: &str, access_token: Option<&str>) -> StatusCode {
+        request(app, http::Method::GET, uri, access_token, Value::Null)
+            .await
+            .0
+    }
+
+    #[tokio::test]
+    async fn test_api_routes_require_their_permission() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let user = find_or_create_user(&app_state, "guarded-route-user", false).await;
+        let permission = find_or_create_permission(&app_state, "read_user_roles").await;
+
+        user_permission::Entity::delete_many()
+            .filter(user_permission::Column::UserId.eq(user.id))
+            .exec(&app_state.db)
+            .await
+            .unwrap();
+
+        let access_token = access_token(&app_state, user.id).await;
+
+        let app = create_router(app_state.clone()).await;
+
+        // requests without credentials are turned away before any handler
+        assert_eq!(
+            get_status(&app, "/api/user_roles", None).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            get_status(&app, "/api/user_roles", Some(&access_token)).await,
+            StatusCode::FORBIDDEN
+        );
+
+        user_permission::ActiveModel {
+            id: NotSet,
+            user_id: Set(user.id),
+            permission_id: Set(permission.id),
+        }
+        .insert(&app_state.db)
+        .await
+        .unwrap();
+
+        // granted behind the API's back, which is what the permission endpoints invalidate for
+        app_state.permission_cache.invalidate_user(user.id);
+
+        assert_eq!(
+            get_status(&app, "/api/user_roles", Some(&access_token)).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            get_status(&app, "/api/users", Some(&access_token)).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+}
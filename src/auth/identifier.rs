@@ -0,0 +1,101 @@
+use sea_orm::sea_query::{Expr, Func, SimpleExpr};
+use unicode_normalization::UnicodeNormalization as _;
+
+use crate::models::_entities::user;
+
+/// Normalization and lookups of the usernames and email addresses users sign in with.
+///
+/// Both are trimmed when written, usernames are NFKC normalized and emails lowercased. Lookups
+/// ignore case through the `lower(..)` unique indexes on both columns. SQLite's `lower` only folds
+/// ASCII letters, so lookups fold usernames the same way to agree with the index.
+pub struct Identifier;
+
+impl Identifier {
+    pub fn normalize_username(username: &str) -> String {
+        username.trim().nfkc().collect()
+    }
+
+    pub fn normalize_email(email: &str) -> String {
+        email.trim().to_lowercase()
+    }
+
+    /// Validation rule for usernames, which can't contain '@' so that signing in never mistakes
+    /// one for an email address.
+    pub fn validate_username<C>(value: &str, _context: &C) -> garde::Result {
+        if value.contains('@') {
+            return Err(garde::Error::new("Username can't contain '@'."));
+        }
+
+        Ok(())
+    }
+
+    /// Matches the user with this username, ignoring case.
+    pub fn username_eq(username: &str) -> SimpleExpr {
+        Expr::expr(Func::lower(Expr::col((
+            user::Entity,
+            user::Column::Username,
+        ))))
+        .eq(Self::normalize_username(username).to_ascii_lowercase())
+    }
+
+    /// Matches the user with this email address, ignoring case.
+    pub fn email_eq(email: &str) -> SimpleExpr {
+        Expr::expr(Func::lower(Expr::col((user::Entity, user::Column::Email))))
+            .eq(Self::normalize_email(email))
+    }
+
+    /// Matches the user signing in, by email address when the identifier looks like one and by
+    /// username otherwise.
+    pub fn login_eq(identifier: &str) -> SimpleExpr {
+        if identifier.contains('@') {
+            Self::email_eq(identifier)
+        } else {
+            Self::username_eq(identifier)
+        }
+    }
+
+    /// The form of a login identifier that lookups compare, so that spellings of the same
+    /// identifier share their failed login throttling.
+    pub fn login_key(identifier: &str) -> String {
+        if identifier.contains('@') {
+            Self::normalize_email(identifier)
+        } else {
+            Self::normalize_username(identifier).to_ascii_lowercase()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifier_normalization() {
+        assert_eq!(
+            Identifier::normalize_email("  Alice@Example.COM "),
+            "alice@example.com"
+        );
+
+        // compatibility forms collapse to the same username
+        assert_eq!(
+            Identifier::normalize_username(" ｆｕｌｌ\u{fb01}x "),
+            "fullfix"
+        );
+        assert_eq!(Identifier::normalize_username("Alice"), "Alice");
+
+        assert_eq!(
+            Identifier::login_key("ALICE"),
+            Identifier::login_key("alice")
+        );
+        assert_eq!(
+            Identifier::login_key("Alice@Example.com"),
+            Identifier::login_key("alice@example.COM")
+        );
+    }
+
+    #[test]
+    fn test_username_rule() {
+        assert!(Identifier::validate_username("alice", &()).is_ok());
+        assert!(Identifier::validate_username("alice@example.com", &()).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use sea_orm::EntityTrait as _;
+
+use crate::{
+    AppState,
+    auth::{
+        auth_service::{AuthService, AuthUser},
+        jwt::{TokenClaims, TokenType, UserToken},
+        token_store::TokenStore,
+    },
+    error::AppError,
+    models::_entities::user,
+};
+
+const IMPERSONATE_PERMISSION: &str = "impersonate_users";
+
+/// Lets support staff act as another user.
+///
+/// Impersonation tokens are short-lived access tokens for the impersonated user whose `act`
+/// claim names the staff member. They can't be refreshed, and are rejected as soon as the staff
+/// member loses the right to impersonate or has their tokens revoked.
+pub struct Impersonation;
+
+impl Impersonation {
+    pub async fn start(
+        ctx: &Arc<AppState>,
+        actor: &AuthUser,
+        user_id: i32,
+    ) -> Result<UserToken, AppError> {
+        Self::ensure_not_impersonating(actor)?;
+        AuthService::has_permission(ctx, actor, IMPERSONATE_PERMISSION).await?;
+
+        let user = user::Entity::find_by_id(user_id)
+            .one(&ctx.db)
+            .await?
+            .ok_or(sea_orm::DbErr::RecordNotFound(
+                "User not found.".to_string(),
+            ))?;
+
+        // impersonating a superadmin would hand out their privileges
+        if user.id == actor.id || (user.is_superadmin && !actor.is_superadmin) {
+            return Err(AppError::Forbidden);
+        }
+
+        let token_claims = TokenClaims::for_user(
+            &user,
+            TokenType::Access,
+            ctx.config.impersonation_expiration_minutes,
+        )
+        .with_actor(actor);
+
+        let access_token = ctx
+            .jwt_keys
+            .encode(&token_claims)
+            .map_err(AppError::GenericError)?;
+
+        tracing::info!(
+            impersonator_id = actor.id,
+            user_id = user.id,
+            jti = token_claims.jti,
+            "impersonation started"
+        );
+
+        Ok(UserToken {
+            access_token,
+            refresh_token: None,
+        })
+    }
+
+    /// Returns the user acting through an impersonation token, checking they still may.
+    pub async fn impersonator(
+        ctx: &Arc<AppState>,
+        token_claims: &TokenClaims,
+    ) -> Result<Option<user::Model>, AppError> {
+        let Some(actor) = &token_claims.act else {
+            return Ok(None);
+        };
+
+        let impersonator_id: i32 = actor.sub.parse().map_err(|_| AppError::InvalidToken)?;
+
+        let impersonator = user::Entity::find_by_id(impersonator_id)
+            .one(&ctx.db)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+
+        if TokenStore::is_revoked(&ctx.db, token_claims, impersonator.id).await? {
+            return Err(AppError::InvalidToken);
+        }
+
+        let impersonator = AuthUser {
+            user: impersonator,
+            api_key: None,
+            impersonator: None,
+        };
+
+        AuthService::has_permission(ctx, &impersonator, IMPERSONATE_PERMISSION)
+            .await
+            .map_err(|_| AppError::InvalidToken)?;
+
+        Ok(Some(impersonator.user))
+    }
+
+    /// Rejects actions impersonated sessions must not take, like changing passwords.
+    pub fn ensure_not_impersonating(auth_user: &AuthUser) -> Result<(), AppError> {
+        match auth_user.impersonator {
+            Some(_) => Err(AppError::Forbidden),
+            None => Ok(()),
+        }
+    }
+}
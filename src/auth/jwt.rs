@@ -1,12 +1,112 @@
 use chrono::{Duration, Utc};
-use jsonwebtoken::{encode, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::{error::AppError, models::_entities::user};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenType {
+    Access,
+    Refresh,
+    EmailVerification,
+    TwoFactorChallenge,
+    MagicLink,
+    EmailChange,
+}
+
+impl TokenType {
+    /// The JWT `typ` header of the type. Access tokens use the `at+jwt` type of RFC 9068, so
+    /// services verifying them against the published keys can tell them from the other tokens.
+    pub fn jwt_type(self) -> &'static str {
+        match self {
+            TokenType::Access => "at+jwt",
+            TokenType::Refresh => "refresh+jwt",
+            TokenType::EmailVerification => "email-verification+jwt",
+            TokenType::TwoFactorChallenge => "2fa-challenge+jwt",
+            TokenType::MagicLink => "magic-link+jwt",
+            TokenType::EmailChange => "email-change+jwt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenClaims {
+    /// The user's id, except for email verification tokens which name the address to verify.
     pub sub: String,
     pub iat: usize,
     pub exp: usize,
+    pub jti: String,
+    pub token_type: TokenType,
+    /// Token family (one per login) the token belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fam: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    /// The user acting on behalf of the subject, set on impersonation tokens (RFC 8693).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    /// The user's `token_version` when the token was issued; bumping it invalidates the token.
+    #[serde(default)]
+    pub ver: i32,
+    /// The new address an email change token confirms.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: String,
+}
+
+impl TokenClaims {
+    pub fn new(subject: &str, token_type: TokenType, expire_in_minutes: i64) -> Self {
+        let now = Utc::now();
+
+        Self {
+            sub: subject.to_string(),
+            iat: now.timestamp() as usize,
+            exp: (now + Duration::minutes(expire_in_minutes)).timestamp() as usize,
+            jti: uuid::Uuid::new_v4().to_string(),
+            token_type,
+            fam: None,
+            iss: None,
+            aud: None,
+            act: None,
+            ver: 0,
+            email: None,
+        }
+    }
+
+    /// Claims identifying the user by their immutable id and current token version.
+    pub fn for_user(user: &user::Model, token_type: TokenType, expire_in_minutes: i64) -> Self {
+        Self {
+            ver: user.token_version,
+            ..Self::new(&user.id.to_string(), token_type, expire_in_minutes)
+        }
+    }
+
+    pub fn user_id(&self) -> Result<i32, AppError> {
+        self.sub.parse().map_err(|_| AppError::InvalidToken)
+    }
+
+    pub fn with_family(mut self, family_id: i32) -> Self {
+        self.fam = Some(family_id);
+        self
+    }
+
+    pub fn with_email(mut self, email: &str) -> Self {
+        self.email = Some(email.to_string());
+        self
+    }
+
+    pub fn with_actor(mut self, actor: &user::Model) -> Self {
+        self.act = Some(Actor {
+            sub: actor.id.to_string(),
+        });
+        self
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -15,27 +115,8 @@ pub struct UserToken {
     pub refresh_token: Option<String>,
 }
 
-pub async fn create_user_token(
-    subject: &str,
-    expire_in_minutes: i64,
-    jwt_secret: &str,
-) -> Result<String, String> {
-    let now = Utc::now();
-    let iat = now.timestamp() as usize;
-    let exp = (now + Duration::minutes(expire_in_minutes)).timestamp() as usize;
-
-    let token_claims = TokenClaims {
-        sub: subject.to_string(),
-        iat,
-        exp,
-    };
-
-    let access_token = encode(
-        &Header::default(),
-        &token_claims,
-        &EncodingKey::from_secret(jwt_secret.as_ref()),
-    )
-    .map_err(|e| e.to_string());
-
-    access_token
+/// Returned by `login` instead of a `UserToken` when the user has 2FA enabled.
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallenge {
+    pub challenge_token: String,
 }
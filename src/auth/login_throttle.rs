@@ -0,0 +1,359 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait as _, ColumnTrait as _, Condition, DbErr, EntityTrait as _, QueryFilter as _,
+    Set, TransactionTrait as _, sea_query::Expr,
+};
+
+use crate::{AppState, configgg::AppConfig, error::AppError, models::_entities::user};
+
+/// Failed login attempts of an account, a client IP or an unknown username.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FailedAttempts {
+    pub failures: u32,
+    pub last_failure_at: Option<NaiveDateTime>,
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+impl FailedAttempts {
+    fn locked_at(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.locked_until.filter(|locked_until| *locked_until > now)
+    }
+
+    /// Failures count towards a lockout until `window` has passed since the last failure or
+    /// the end of the last lockout, whichever is later.
+    fn is_current(&self, now: NaiveDateTime, window: Duration) -> bool {
+        self.locked_until
+            .max(self.last_failure_at)
+            .is_some_and(|last_activity| now - last_activity <= window)
+    }
+}
+
+impl From<&user::Model> for FailedAttempts {
+    fn from(value: &user::Model) -> Self {
+        Self {
+            failures: value.failed_login_attempts.max(0) as u32,
+            last_failure_at: value.last_failed_login_at,
+            locked_until: value.locked_until,
+        }
+    }
+}
+
+/// Lockout thresholds with exponential backoff: once `max_attempts` is reached, every further
+/// failure locks for twice as long as the previous one, up to `max_lockout`.
+#[derive(Debug, Clone)]
+pub struct LockoutPolicy {
+    pub max_attempts: u32,
+    pub base_lockout: Duration,
+    pub max_lockout: Duration,
+    pub window: Duration,
+}
+
+impl LockoutPolicy {
+    pub fn for_accounts(app_config: &AppConfig) -> Self {
+        Self::new(app_config, app_config.login_max_attempts)
+    }
+
+    pub fn for_client_ips(app_config: &AppConfig) -> Self {
+        Self::new(app_config, app_config.login_ip_max_attempts)
+    }
+
+    fn new(app_config: &AppConfig, max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_lockout: Duration::seconds(app_config.login_lockout_base_seconds),
+            max_lockout: Duration::seconds(app_config.login_lockout_max_seconds),
+            window: Duration::seconds(app_config.login_attempt_window_seconds),
+        }
+    }
+
+    pub fn register_failure(
+        &self,
+        attempts: &FailedAttempts,
+        now: NaiveDateTime,
+    ) -> FailedAttempts {
+        let failures = match attempts.is_current(now, self.window) {
+            true => attempts.failures + 1,
+            false => 1,
+        };
+
+        FailedAttempts {
+            failures,
+            last_failure_at: Some(now),
+            locked_until: self.lockout(failures, now),
+        }
+    }
+
+    /// End of the lockout `failures` current failures lead to, if they are enough for one.
+    pub fn lockout(&self, failures: u32, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        (failures >= self.max_attempts).then(|| {
+            let exponent = (failures - self.max_attempts).min(20);
+            now + (self.base_lockout * (1 << exponent)).min(self.max_lockout)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ThrottleKey {
+    ClientIp(IpAddr),
+    /// Usernames without an account are throttled like accounts, so lockouts don't reveal
+    /// which usernames exist.
+    UnknownUsername(String),
+}
+
+/// Most client IPs and unknown usernames tracked at once, so spraying random usernames can't
+/// grow the map without bound.
+const MAX_THROTTLED_KEYS: usize = 10_000;
+
+/// In-memory failed attempts of client IPs and unknown usernames.
+#[derive(Debug, Default)]
+pub struct LoginThrottle {
+    attempts: Mutex<HashMap<ThrottleKey, FailedAttempts>>,
+}
+
+impl LoginThrottle {
+    fn locked_at(&self, key: &ThrottleKey, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let attempts = self.attempts.lock().unwrap();
+
+        attempts
+            .get(key)
+            .and_then(|attempts| attempts.locked_at(now))
+    }
+
+    fn register_failure(
+        &self,
+        key: ThrottleKey,
+        policy: &LockoutPolicy,
+        now: NaiveDateTime,
+    ) -> Option<NaiveDateTime> {
+        let mut attempts = self.attempts.lock().unwrap();
+
+        // forget entries that no longer count towards a lockout
+        attempts.retain(|_, attempts| attempts.is_current(now, policy.window));
+
+        if attempts.len() >= MAX_THROTTLED_KEYS && !attempts.contains_key(&key) {
+            let oldest = attempts
+                .iter()
+                .min_by_key(|(_, attempts)| attempts.last_failure_at)
+                .map(|(key, _)| key.clone());
+
+            if let Some(oldest) = oldest {
+                attempts.remove(&oldest);
+            }
+        }
+
+        let updated = policy.register_failure(&attempts.remove(&key).unwrap_or_default(), now);
+        let locked_until = updated.locked_until;
+        attempts.insert(key, updated);
+
+        locked_until
+    }
+}
+
+/// Applies the lockout policies to login attempts.
+pub struct LoginGuard;
+
+impl LoginGuard {
+    /// Fails with `TooManyLoginAttempts` while the client IP or the account is locked.
+    pub fn check(
+        ctx: &Arc<AppState>,
+        client_ip: Option<IpAddr>,
+        username: &str,
+        user: Option<&user::Model>,
+    ) -> Result<(), AppError> {
+        let now = Utc::now().naive_utc();
+
+        let ip_locked_until = client_ip.and_then(|client_ip| {
+            ctx.login_throttle
+                .locked_at(&ThrottleKey::ClientIp(client_ip), now)
+        });
+
+        let account_locked_until = match user {
+            Some(user) => FailedAttempts::from(user).locked_at(now),
+            None => ctx
+                .login_throttle
+                .locked_at(&ThrottleKey::UnknownUsername(username.to_string()), now),
+        };
+
+        match ip_locked_until.max(account_locked_until) {
+            Some(locked_until) => Err(AppError::TooManyLoginAttempts(locked_until.and_utc())),
+            None => Ok(()),
+        }
+    }
+
+    /// Counts a failed attempt and returns the error to respond with.
+    pub async fn register_failure(
+        ctx: &Arc<AppState>,
+        client_ip: Option<IpAddr>,
+        username: &str,
+        user: Option<user::Model>,
+    ) -> AppError {
+        let now = Utc::now().naive_utc();
+
+        let ip_locked_until = client_ip.and_then(|client_ip| {
+            ctx.login_throttle.register_failure(
+                ThrottleKey::ClientIp(client_ip),
+                &LockoutPolicy::for_client_ips(&ctx.config),
+                now,
+            )
+        });
+
+        let account_policy = LockoutPolicy::for_accounts(&ctx.config);
+        let account_locked_until = match user {
+            Some(user) => {
+                match register_account_failure(ctx, &account_policy, user.id, now).await {
+                    Ok(locked_until) => locked_until,
+                    Err(e) => return e,
+                }
+            }
+            None => ctx.login_throttle.register_failure(
+                ThrottleKey::UnknownUsername(username.to_string()),
+                &account_policy,
+                now,
+            ),
+        };
+
+        match ip_locked_until.max(account_locked_until) {
+            Some(locked_until) => AppError::TooManyLoginAttempts(locked_until.and_utc()),
+            None => AppError::InvalidCredentials,
+        }
+    }
+
+    /// Clears the failed attempts of the account after a successful login.
+    pub async fn register_success(
+        ctx: &Arc<AppState>,
+        user: user::Model,
+    ) -> Result<user::Model, AppError> {
+        if user.failed_login_attempts == 0 && user.locked_until.is_none() {
+            return Ok(user);
+        }
+
+        let mut user: user::ActiveModel = user.into();
+        user.failed_login_attempts = Set(0);
+        user.last_failed_login_at = Set(None);
+        user.locked_until = Set(None);
+
+        Ok(user.update(&ctx.db).await?)
+    }
+}
+
+/// Counts a failure of the account with a single increment, so concurrent failures all count,
+/// then locks it according to the stored count.
+async fn register_account_failure(
+    ctx: &Arc<AppState>,
+    policy: &LockoutPolicy,
+    user_id: i32,
+    now: NaiveDateTime,
+) -> Result<Option<NaiveDateTime>, AppError> {
+    let cutoff = now - policy.window;
+
+    // the increment takes SQLite's write lock, which the transaction holds until the lockout
+    // below is written
+    let txn = ctx.db.begin().await?;
+
+    user::Entity::update_many()
+        .col_expr(
+            user::Column::FailedLoginAttempts,
+            Expr::case(
+                Condition::any()
+                    .add(user::Column::LastFailedLoginAt.gte(cutoff))
+                    .add(user::Column::LockedUntil.gte(cutoff)),
+                Expr::col(user::Column::FailedLoginAttempts).add(1),
+            )
+            .finally(1)
+            .into(),
+        )
+        .col_expr(user::Column::LastFailedLoginAt, Expr::value(now))
+        .filter(user::Column::Id.eq(user_id))
+        .exec(&txn)
+        .await?;
+
+    let user = user::Entity::find_by_id(user_id)
+        .one(&txn)
+        .await?
+        .ok_or(DbErr::RecordNotFound("User not found.".to_string()))?;
+
+    let locked_until = policy.lockout(user.failed_login_attempts.max(0) as u32, now);
+
+    if locked_until.is_some() {
+        let mut user: user::ActiveModel = user.into();
+        user.locked_until = Set(locked_until);
+        user.update(&txn).await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(locked_until)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::{FailedAttempts, LockoutPolicy, LoginThrottle, MAX_THROTTLED_KEYS, ThrottleKey};
+
+    fn policy() -> LockoutPolicy {
+        LockoutPolicy {
+            max_attempts: 3,
+            base_lockout: Duration::seconds(30),
+            max_lockout: Duration::seconds(100),
+            window: Duration::seconds(900),
+        }
+    }
+
+    #[test]
+    fn test_lockout_backs_off_exponentially() {
+        let policy = policy();
+        let now = Utc::now().naive_utc();
+
+        let mut attempts = FailedAttempts::default();
+        let mut lockouts = Vec::new();
+
+        for _ in 0..5 {
+            attempts = policy.register_failure(&attempts, now);
+            lockouts.push(
+                attempts
+                    .locked_until
+                    .map(|locked_until| (locked_until - now).num_seconds()),
+            );
+        }
+
+        assert_eq!(lockouts, [None, None, Some(30), Some(60), Some(100)]);
+    }
+
+    #[test]
+    fn test_failures_are_forgotten_after_the_window() {
+        let policy = policy();
+        let now = Utc::now().naive_utc();
+
+        let attempts = FailedAttempts {
+            failures: 2,
+            last_failure_at: Some(now - Duration::seconds(901)),
+            locked_until: None,
+        };
+
+        assert_eq!(policy.register_failure(&attempts, now).failures, 1);
+    }
+
+    #[test]
+    fn test_throttled_keys_are_capped() {
+        let policy = policy();
+        let now = Utc::now().naive_utc();
+        let throttle = LoginThrottle::default();
+
+        for i in 0..=MAX_THROTTLED_KEYS {
+            let key = ThrottleKey::UnknownUsername(format!("sprayed-{i}"));
+            throttle.register_failure(key, &policy, now + Duration::milliseconds(i as i64));
+        }
+
+        let attempts = throttle.attempts.lock().unwrap();
+
+        assert_eq!(attempts.len(), MAX_THROTTLED_KEYS);
+        assert!(!attempts.contains_key(&ThrottleKey::UnknownUsername("sprayed-0".to_string())));
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait as _, Set, TransactionTrait as _};
+
+use crate::{
+    AppState,
+    auth::{
+        jwt::{TokenClaims, TokenType},
+        token_store::TokenStore,
+    },
+    error::AppError,
+    models::_entities::user,
+};
+
+/// Passwordless sign in, enabled with `magic_link_login`.
+///
+/// The emailed link carries a short-lived `magic_link` token. Redeeming it revokes the token, so
+/// a link signs in once. Opening the link only shows a page that redeems it with a `POST`, so
+/// mail scanners and prefetchers following the link don't use it up.
+pub struct MagicLink;
+
+impl MagicLink {
+    pub fn login_url(ctx: &Arc<AppState>, user: &user::Model) -> Result<String, AppError> {
+        let token_claims = TokenClaims::for_user(
+            user,
+            TokenType::MagicLink,
+            ctx.config.magic_link_expiration_minutes,
+        );
+
+        let token = ctx
+            .jwt_keys
+            .encode(&token_claims)
+            .map_err(AppError::GenericError)?;
+
+        Ok(format!(
+            "{}/api/auth/magic-link/verify?token={}",
+            ctx.config.app_url.trim_end_matches('/'),
+            token
+        ))
+    }
+
+    /// Redeems a link for the user it was sent to.
+    pub async fn consume(ctx: &Arc<AppState>, token: &str) -> Result<user::Model, AppError> {
+        let token_claims = ctx.jwt_keys.decode(token, TokenType::MagicLink)?;
+
+        let user = TokenStore::authenticate(&ctx.db, &token_claims).await?;
+
+        let txn = ctx.db.begin().await?;
+
+        // only one of concurrent redemptions of the link gets past this
+        TokenStore::redeem(&txn, &token_claims, user.id).await?;
+
+        let user = match user.email_verified_at {
+            Some(_) => user,
+            None => {
+                // receiving the link proves ownership of the address
+                let mut user: user::ActiveModel = user.into();
+                user.email_verified_at = Set(Some(Utc::now().naive_utc()));
+                user.update(&txn).await?
+            }
+        };
+
+        txn.commit().await?;
+
+        Ok(user)
+    }
+}
@@ -1,2 +1,23 @@
+pub mod api_key_store;
+pub mod auth_cookies;
 pub mod auth_service;
+pub mod deactivation;
+pub mod email_change;
+pub mod email_verification;
+pub mod guarded_router;
+pub mod identifier;
+pub mod impersonation;
 pub mod jwt;
+pub mod jwt_keys;
+pub mod login_throttle;
+pub mod magic_link;
+pub mod oidc;
+pub mod password;
+pub mod password_policy;
+pub mod password_reset;
+pub mod permission_cache;
+pub mod permission_grant;
+pub mod role_hierarchy;
+pub mod token_store;
+pub mod totp;
+pub mod two_factor;
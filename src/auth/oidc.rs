@@ -0,0 +1,460 @@
+use std::sync::Arc;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore as _};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header, jwk::JwkSet};
+use reqwest::Url;
+use sea_orm::{
+    ActiveModelTrait as _, ActiveValue::NotSet, ColumnTrait as _, ConnectionTrait, DbErr,
+    EntityTrait as _, QueryFilter as _, Set, TransactionTrait as _,
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use sha2::{Digest as _, Sha256};
+
+use crate::{
+    AppState,
+    auth::{identifier::Identifier, password::PasswordHasher},
+    configgg::AppConfig,
+    error::AppError,
+    models::_entities::{oauth_state, user, user_identity},
+};
+
+/// An OpenID Connect provider users can sign in with.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcProvider {
+    /// Identifies the provider in the `/api/auth/oauth/{provider}` routes.
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    /// Whether a first sign in with an email the provider verified signs in to the existing
+    /// account with that email. Only enable it for providers trusted to verify addresses.
+    #[serde(default)]
+    pub link_existing_accounts: bool,
+}
+
+fn default_scopes() -> Vec<String> {
+    ["openid", "email", "profile"].map(str::to_string).to_vec()
+}
+
+/// The configured providers and the HTTP client used to talk to them.
+#[derive(Debug)]
+pub struct OidcProviders {
+    providers: Vec<OidcProvider>,
+    http_client: reqwest::Client,
+}
+
+impl OidcProviders {
+    pub fn from_config(app_config: &AppConfig) -> Result<Self, String> {
+        let providers: Vec<OidcProvider> = match app_config.oauth_providers.as_deref() {
+            Some(providers) if !providers.trim().is_empty() => serde_json::from_str(providers)
+                .map_err(|e| format!("Invalid OAUTH_PROVIDERS: {e}"))?,
+            _ => Vec::new(),
+        };
+
+        let http_client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            providers,
+            http_client,
+        })
+    }
+
+    fn get(&self, name: &str) -> Result<&OidcProvider, AppError> {
+        self.providers
+            .iter()
+            .find(|provider| provider.name == name)
+            .ok_or_else(|| DbErr::RecordNotFound("Unknown identity provider.".to_string()).into())
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, AppError> {
+        self.http_client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AppError::IdentityProviderError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| AppError::IdentityProviderError(e.to_string()))
+    }
+}
+
+/// Subset of the provider metadata served from `/.well-known/openid-configuration`.
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenEndpointResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+    name: Option<String>,
+    preferred_username: Option<String>,
+}
+
+/// Sign in with an OpenID Connect provider, using the authorization code flow with PKCE.
+///
+/// The state, nonce and PKCE verifier of a sign in are stored until the provider redirects
+/// back, and can only be used once, by the browser the state cookie was set in. External identities are linked to users through
+/// `user_identity`; users signing in for the first time get an account created for them, or
+/// their existing account when the provider has `link_existing_accounts` enabled.
+pub struct OidcLogin;
+
+impl OidcLogin {
+    /// Returns the URL of the provider's authorization endpoint to send the user to, and the
+    /// state the provider redirects back with, to be bound to the user's browser.
+    pub async fn authorization_url(
+        ctx: &Arc<AppState>,
+        provider_name: &str,
+    ) -> Result<(String, String), AppError> {
+        let provider = ctx.oidc_providers.get(provider_name)?;
+        let metadata = Self::metadata(ctx, provider).await?;
+
+        Self::purge_expired(&ctx.db).await?;
+
+        let state = random_token();
+        let nonce = random_token();
+        let code_verifier = random_token();
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        let now = Utc::now();
+        let expires_at = now + Duration::minutes(ctx.config.oauth_state_expiration_minutes);
+
+        oauth_state::ActiveModel {
+            id: NotSet,
+            provider: Set(provider.name.clone()),
+            state: Set(state.clone()),
+            code_verifier: Set(code_verifier),
+            nonce: Set(nonce.clone()),
+            expires_at: Set(expires_at.naive_utc()),
+            date_created: Set(now.naive_utc()),
+        }
+        .insert(&ctx.db)
+        .await?;
+
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            [
+                ("response_type", "code"),
+                ("client_id", &provider.client_id),
+                ("redirect_uri", &redirect_uri(&ctx.config, provider)),
+                ("scope", &provider.scopes.join(" ")),
+                ("state", &state),
+                ("nonce", &nonce),
+                ("code_challenge", &code_challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| AppError::IdentityProviderError(e.to_string()))?;
+
+        Ok((url.into(), state))
+    }
+
+    /// Redeems the authorization code the provider redirected back with and returns the user
+    /// it belongs to.
+    pub async fn complete(
+        ctx: &Arc<AppState>,
+        provider_name: &str,
+        code: &str,
+        state: &str,
+    ) -> Result<user::Model, AppError> {
+        let provider = ctx.oidc_providers.get(provider_name)?;
+        let oauth_state = Self::take_state(&ctx.db, provider, state).await?;
+        let metadata = Self::metadata(ctx, provider).await?;
+
+        let redirect_uri = redirect_uri(&ctx.config, provider);
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &redirect_uri),
+            ("client_id", &provider.client_id),
+            ("code_verifier", &oauth_state.code_verifier),
+        ];
+
+        if let Some(client_secret) = &provider.client_secret {
+            form.push(("client_secret", client_secret));
+        }
+
+        let token_response: TokenEndpointResponse = ctx
+            .oidc_providers
+            .http_client
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AppError::IdentityProviderError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| AppError::IdentityProviderError(e.to_string()))?;
+
+        let jwks: JwkSet = ctx.oidc_providers.get_json(&metadata.jwks_uri).await?;
+        let claims = verify_id_token(
+            &token_response.id_token,
+            &jwks,
+            &metadata.issuer,
+            &provider.client_id,
+        )?;
+
+        if claims.nonce.as_deref() != Some(oauth_state.nonce.as_str()) {
+            return Err(AppError::IdentityProviderError(
+                "ID token nonce does not match".to_string(),
+            ));
+        }
+
+        Self::link_user(ctx, provider, claims).await
+    }
+
+    async fn metadata(
+        ctx: &Arc<AppState>,
+        provider: &OidcProvider,
+    ) -> Result<ProviderMetadata, AppError> {
+        let issuer = provider.issuer.trim_end_matches('/');
+        let metadata: ProviderMetadata = ctx
+            .oidc_providers
+            .get_json(&format!("{issuer}/.well-known/openid-configuration"))
+            .await?;
+
+        if metadata.issuer.trim_end_matches('/') != issuer {
+            return Err(AppError::IdentityProviderError(format!(
+                "Provider metadata is for issuer {}",
+                metadata.issuer
+            )));
+        }
+
+        Ok(metadata)
+    }
+
+    /// Consumes the state of a sign in, so a redirect can't be replayed.
+    async fn take_state<C: ConnectionTrait>(
+        db: &C,
+        provider: &OidcProvider,
+        state: &str,
+    ) -> Result<oauth_state::Model, AppError> {
+        let oauth_state = oauth_state::Entity::find()
+            .filter(oauth_state::Column::State.eq(state))
+            .filter(oauth_state::Column::Provider.eq(&provider.name))
+            .one(db)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+
+        let res = oauth_state::Entity::delete_by_id(oauth_state.id)
+            .exec(db)
+            .await?;
+
+        if res.rows_affected == 0 || oauth_state.expires_at <= Utc::now().naive_utc() {
+            return Err(AppError::InvalidToken);
+        }
+
+        Ok(oauth_state)
+    }
+
+    /// Removes the states of sign ins that were abandoned before the provider redirected back.
+    async fn purge_expired<C: ConnectionTrait>(db: &C) -> Result<(), AppError> {
+        oauth_state::Entity::delete_many()
+            .filter(oauth_state::Column::ExpiresAt.lt(Utc::now().naive_utc()))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn link_user(
+        ctx: &Arc<AppState>,
+        provider: &OidcProvider,
+        claims: IdTokenClaims,
+    ) -> Result<user::Model, AppError> {
+        let now = Utc::now().naive_utc();
+        let txn = ctx.db.begin().await?;
+
+        let identity = user_identity::Entity::find()
+            .filter(user_identity::Column::Provider.eq(&provider.name))
+            .filter(user_identity::Column::Subject.eq(&claims.sub))
+            .one(&txn)
+            .await?;
+
+        if let Some(identity) = identity {
+            let user = user::Entity::find_by_id(identity.user_id)
+                .one(&txn)
+                .await?
+                .ok_or(AppError::InvalidToken)?;
+
+            let mut identity: user_identity::ActiveModel = identity.into();
+            identity.email = Set(claims.email);
+            identity.last_login_at = Set(Some(now));
+            identity.update(&txn).await?;
+
+            txn.commit().await?;
+
+            return Ok(user);
+        }
+
+        let email = claims.email.clone().ok_or(AppError::GenericError(
+            "The identity provider did not share an email address.".to_string(),
+        ))?;
+
+        let existing_user = user::Entity::find()
+            .filter(Identifier::email_eq(&email))
+            .one(&txn)
+            .await?;
+
+        let user = match existing_user {
+            // only an address the provider verified proves the account belongs to the same person
+            Some(user) if provider.link_existing_accounts && claims.email_verified => user,
+            Some(_) => {
+                return Err(AppError::GenericError(
+                    "A user with this email already exists.".to_string(),
+                ));
+            }
+            None => {
+                let username = available_username(&txn, &claims, &email).await?;
+
+                // the account can only be signed in to through the provider until a password
+                // is reset
+                let password_hash = PasswordHasher::new(&ctx.config)?
+                    .hash(&random_token())
+                    .await?;
+
+                user::ActiveModel {
+                    name: Set(claims.name.clone().unwrap_or_else(|| username.clone())),
+                    username: Set(username),
+                    email: Set(email),
+                    password: Set(password_hash),
+                    is_superadmin: Set(false),
+                    date_created: Set(now),
+                    email_verified_at: Set(claims.email_verified.then_some(now)),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?
+            }
+        };
+
+        user_identity::ActiveModel {
+            id: NotSet,
+            user_id: Set(user.id),
+            provider: Set(provider.name.clone()),
+            subject: Set(claims.sub),
+            email: Set(claims.email),
+            last_login_at: Set(Some(now)),
+            date_created: Set(now),
+        }
+        .insert(&txn)
+        .await?;
+
+        txn.commit().await?;
+
+        Ok(user)
+    }
+}
+
+fn redirect_uri(app_config: &AppConfig, provider: &OidcProvider) -> String {
+    format!(
+        "{}/api/auth/oauth/{}/callback",
+        app_config.app_url.trim_end_matches('/'),
+        provider.name
+    )
+}
+
+fn verify_id_token(
+    id_token: &str,
+    jwks: &JwkSet,
+    issuer: &str,
+    client_id: &str,
+) -> Result<IdTokenClaims, AppError> {
+    let header = decode_header(id_token).map_err(|_| AppError::InvalidToken)?;
+
+    // ID tokens must be signed with one of the provider's published keys
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(AppError::InvalidToken);
+    }
+
+    let jwk = match &header.kid {
+        Some(key_id) => jwks.find(key_id),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .ok_or(AppError::InvalidToken)?;
+
+    let decoding_key = DecodingKey::from_jwk(jwk).map_err(|_| AppError::InvalidToken)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    decode::<IdTokenClaims>(id_token, &decoding_key, &validation)
+        .map(|token_data| token_data.claims)
+        .map_err(|e| {
+            tracing::error!("{:#?}", e);
+            AppError::InvalidToken
+        })
+}
+
+/// Derives a username from the provider's claims, adding a number when it is taken.
+async fn available_username<C: ConnectionTrait>(
+    db: &C,
+    claims: &IdTokenClaims,
+    email: &str,
+) -> Result<String, AppError> {
+    let base: String = claims
+        .preferred_username
+        .as_deref()
+        .unwrap_or_else(|| email.split('@').next().unwrap_or_default())
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        .take(90)
+        .collect();
+
+    for suffix in 0..100 {
+        let username = match suffix {
+            0 if base.len() >= 5 => base.clone(),
+            0 => continue,
+            _ => format!("{base}-{suffix}"),
+        };
+
+        let taken = user::Entity::find()
+            .filter(Identifier::username_eq(&username))
+            .one(db)
+            .await?
+            .is_some();
+
+        if !taken && username.len() >= 5 {
+            return Ok(username);
+        }
+    }
+
+    Err(AppError::GenericError(
+        "Unable to choose a username for the account.".to_string(),
+    ))
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
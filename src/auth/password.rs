@@ -0,0 +1,216 @@
+use argon2::{
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher as _, PasswordVerifier as _, Version,
+    password_hash::{SaltString, rand_core::OsRng},
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::OnceLock;
+
+use crate::{configgg::AppConfig, error::AppError};
+
+/// Hash verified for unknown users, created along with the `AppState`.
+static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+
+/// Key of the HMAC-SHA256 digests stored before passwords were hashed with Argon2id.
+const LEGACY_HMAC_KEY: &[u8] = b"secret_key";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordVerification {
+    Invalid,
+    Valid,
+    /// The password matched a legacy hash or outdated Argon2 parameters and should be rehashed.
+    ValidNeedsRehash,
+}
+
+/// Hashes passwords with Argon2id into PHC strings, using the cost parameters from `AppConfig`.
+pub struct PasswordHasher {
+    argon2: Argon2<'static>,
+}
+
+impl PasswordHasher {
+    pub fn new(app_config: &AppConfig) -> Result<Self, AppError> {
+        let params = Params::new(
+            app_config.argon2_memory_kib,
+            app_config.argon2_iterations,
+            app_config.argon2_parallelism,
+            None,
+        )
+        .map_err(|e| AppError::GenericError(e.to_string()))?;
+
+        Ok(Self {
+            argon2: Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+        })
+    }
+
+    pub async fn hash(&self, password: &str) -> Result<String, AppError> {
+        let argon2 = self.argon2.clone();
+        let password = password.to_string();
+
+        run_blocking(move || hash_password(&argon2, &password)).await
+    }
+
+    pub async fn verify(
+        &self,
+        password_hash: &str,
+        password: &str,
+    ) -> Result<PasswordVerification, AppError> {
+        let argon2 = self.argon2.clone();
+        let password_hash = password_hash.to_string();
+        let password = password.to_string();
+
+        run_blocking(move || verify_password(&argon2, &password_hash, &password)).await
+    }
+
+    /// Verifies the password against a throwaway hash, so that a login for an unknown user
+    /// takes as long as one with a wrong password.
+    pub async fn verify_dummy(&self, password: &str) -> Result<(), AppError> {
+        let argon2 = self.argon2.clone();
+        let password = password.to_string();
+
+        run_blocking(move || verify_dummy_password(&argon2, &password)).await
+    }
+
+    /// Creates the hash `verify_dummy` checks against, so the first login for an unknown user
+    /// doesn't take longer than the others.
+    pub fn init_dummy_hash(&self) -> Result<(), AppError> {
+        dummy_hash(&self.argon2).map(|_| ())
+    }
+}
+
+/// Runs Argon2 on the blocking thread pool, a hash takes long enough to stall the other
+/// requests sharing a runtime worker.
+async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, AppError> + Send + 'static,
+) -> Result<T, AppError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| AppError::GenericError(e.to_string()))?
+}
+
+fn hash_password(argon2: &Argon2<'static>, password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    argon2
+        .hash_password(password.as_bytes(), &salt)
+        .map(|password_hash| password_hash.to_string())
+        .map_err(|e| AppError::GenericError(e.to_string()))
+}
+
+fn dummy_hash(argon2: &Argon2<'static>) -> Result<&'static str, AppError> {
+    if let Some(dummy_hash) = DUMMY_HASH.get() {
+        return Ok(dummy_hash);
+    }
+
+    let dummy_hash = hash_password(argon2, "dummy-password")?;
+
+    Ok(DUMMY_HASH.get_or_init(|| dummy_hash))
+}
+
+fn verify_dummy_password(argon2: &Argon2<'static>, password: &str) -> Result<(), AppError> {
+    verify_password(argon2, dummy_hash(argon2)?, password).map(|_| ())
+}
+
+fn verify_password(
+    argon2: &Argon2<'static>,
+    password_hash: &str,
+    password: &str,
+) -> Result<PasswordVerification, AppError> {
+    let Ok(parsed_hash) = PasswordHash::new(password_hash) else {
+        // the HMAC takes microseconds, a wrong password must cost as much as with Argon2
+        verify_dummy_password(argon2, password)?;
+
+        return Ok(verify_legacy_password(password_hash, password));
+    };
+
+    if argon2
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_err()
+    {
+        return Ok(PasswordVerification::Invalid);
+    }
+
+    let params = argon2.params();
+    let outdated = parsed_hash.algorithm != argon2::ARGON2ID_IDENT
+        || Params::try_from(&parsed_hash).map_or(true, |hash_params| {
+            (
+                hash_params.m_cost(),
+                hash_params.t_cost(),
+                hash_params.p_cost(),
+            ) != (params.m_cost(), params.t_cost(), params.p_cost())
+        });
+
+    if outdated {
+        return Ok(PasswordVerification::ValidNeedsRehash);
+    }
+
+    Ok(PasswordVerification::Valid)
+}
+
+/// Verifies a hex encoded HMAC-SHA256 digest in constant time. No password matches a hash in
+/// any other format, so that a failed login doesn't tell those accounts apart either.
+fn verify_legacy_password(hex_code: &str, password: &str) -> PasswordVerification {
+    let Ok(code_bytes) = hex::decode(hex_code) else {
+        return PasswordVerification::Invalid;
+    };
+
+    let mut mac: Hmac<Sha256> =
+        Hmac::new_from_slice(LEGACY_HMAC_KEY).expect("HMAC can take key of any size");
+
+    mac.update(password.as_bytes());
+
+    match mac.verify_slice(&code_bytes) {
+        Ok(()) => PasswordVerification::ValidNeedsRehash,
+        Err(_) => PasswordVerification::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dotenvy::dotenv;
+
+    use super::{PasswordHasher, PasswordVerification};
+    use crate::configgg::AppConfig;
+
+    #[tokio::test]
+    async fn test_argon2_hash_roundtrip() {
+        dotenv().ok();
+
+        let hasher = PasswordHasher::new(&AppConfig::from_env().unwrap()).unwrap();
+        let password_hash = hasher.hash("correct-horse").await.unwrap();
+
+        assert!(password_hash.starts_with("$argon2id$"));
+        assert_eq!(
+            hasher
+                .verify(&password_hash, "correct-horse")
+                .await
+                .unwrap(),
+            PasswordVerification::Valid
+        );
+        assert_eq!(
+            hasher.verify(&password_hash, "wrong-horse").await.unwrap(),
+            PasswordVerification::Invalid
+        );
+    }
+
+    #[tokio::test]
+    async fn test_legacy_hash_needs_rehash() {
+        dotenv().ok();
+
+        let hasher = PasswordHasher::new(&AppConfig::from_env().unwrap()).unwrap();
+        // HMAC-SHA256 of "correct-horse" as stored before the move to Argon2id
+        let legacy_hash = "2e5f273b4d1c82aae3c0a30cbba695671ccfbca6d97305dfa78be335e7e63380";
+
+        assert_eq!(
+            hasher.verify(legacy_hash, "correct-horse").await.unwrap(),
+            PasswordVerification::ValidNeedsRehash
+        );
+        assert_eq!(
+            hasher.verify(legacy_hash, "wrong-horse").await.unwrap(),
+            PasswordVerification::Invalid
+        );
+        assert_eq!(
+            hasher.verify("", "").await.unwrap(),
+            PasswordVerification::Invalid
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::{collections::HashSet, sync::Arc};
+
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait as _, ActiveValue::NotSet, ColumnTrait as _, ConnectionTrait,
+    EntityTrait as _, QueryFilter as _, QueryOrder as _, QuerySelect as _, Set,
+};
+
+use crate::{
+    AppState,
+    auth::password::{PasswordHasher, PasswordVerification},
+    configgg::AppConfig,
+    error::AppError,
+    models::_entities::{password_history, user},
+};
+
+/// Identifiers shorter than this aren't looked for in passwords, they would match too often.
+const MIN_IDENTIFIER_LENGTH: usize = 3;
+
+/// Whether the class is required, its name and whether a character belongs to it.
+type CharacterClass = (bool, &'static str, fn(char) -> bool);
+
+/// Rules every new password must follow, configured in `AppConfig`.
+#[derive(Debug, Default)]
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    require_lowercase: bool,
+    require_uppercase: bool,
+    require_digit: bool,
+    require_symbol: bool,
+    /// Lowercased breached or common passwords.
+    blocklist: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    pub fn from_config(app_config: &AppConfig) -> Result<Self, String> {
+        let blocklist = match app_config
+            .password_blocklist_path
+            .as_deref()
+            .filter(|path| !path.trim().is_empty())
+        {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read the password blocklist {path}: {e}"))?
+                .lines()
+                .map(|line| line.trim().to_lowercase())
+                .filter(|line| !line.is_empty())
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        Ok(Self {
+            min_length: app_config.password_min_length,
+            max_length: app_config.password_max_length,
+            require_lowercase: app_config.password_require_lowercase,
+            require_uppercase: app_config.password_require_uppercase,
+            require_digit: app_config.password_require_digit,
+            require_symbol: app_config.password_require_symbol,
+            blocklist,
+        })
+    }
+
+    /// Checks a password against the policy. `identifiers` are the username and email address,
+    /// which the password must not contain.
+    pub fn check(&self, password: &str, identifiers: &[&str]) -> garde::Result {
+        let length = password.chars().count();
+
+        if length < self.min_length {
+            return Err(garde::Error::new(format!(
+                "Password must be at least {} characters long.",
+                self.min_length
+            )));
+        }
+
+        if length > self.max_length {
+            return Err(garde::Error::new(format!(
+                "Password must be at most {} characters long.",
+                self.max_length
+            )));
+        }
+
+        let character_classes: [CharacterClass; 4] = [
+            (
+                self.require_lowercase,
+                "a lowercase letter",
+                char::is_lowercase,
+            ),
+            (
+                self.require_uppercase,
+                "an uppercase letter",
+                char::is_uppercase,
+            ),
+            (self.require_digit, "a digit", |c| c.is_ascii_digit()),
+            (self.require_symbol, "a symbol", |c| !c.is_alphanumeric()),
+        ];
+
+        let missing: Vec<&str> = character_classes
+            .into_iter()
+            .filter(|(required, _, matches)| *required && !password.chars().any(matches))
+            .map(|(_, name, _)| name)
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(garde::Error::new(format!(
+                "Password must contain {}.",
+                missing.join(", ")
+            )));
+        }
+
+        let password = password.to_lowercase();
+
+        // the local part of an email address is as guessable as the address
+        let contains_identifier = identifiers
+            .iter()
+            .flat_map(|identifier| [*identifier, identifier.split('@').next().unwrap_or("")])
+            .map(|identifier| identifier.trim().to_lowercase())
+            .any(|identifier| {
+                identifier.chars().count() >= MIN_IDENTIFIER_LENGTH
+                    && password.contains(&identifier)
+            });
+
+        if contains_identifier {
+            return Err(garde::Error::new(
+                "Password must not contain your username or email.",
+            ));
+        }
+
+        if self.blocklist.contains(&password) {
+            return Err(garde::Error::new(
+                "Password is too common, choose a less guessable one.",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// `garde` rule for password fields of requests validated with the app state as context.
+    pub fn rule<'a>(
+        username: &'a str,
+        email: &'a str,
+    ) -> impl FnOnce(&str, &Arc<AppState>) -> garde::Result + 'a {
+        move |password, context| context.password_policy.check(password, &[username, email])
+    }
+
+    /// Checks a password outside of request validation, failing with a `password` field error.
+    pub fn validate(&self, password: &str, identifiers: &[&str]) -> Result<(), AppError> {
+        self.check(password, identifiers).map_err(password_error)
+    }
+}
+
+/// Keeps the hashes of replaced passwords, so users can't go back to a recent password.
+pub struct PasswordHistory;
+
+impl PasswordHistory {
+    /// Fails if the password is the user's current password or one of the passwords it replaced,
+    /// within the last `password_history_size` passwords.
+    pub async fn ensure_not_reused<C: ConnectionTrait>(
+        db: &C,
+        app_config: &AppConfig,
+        user: &user::Model,
+        password: &str,
+    ) -> Result<(), AppError> {
+        if app_config.password_history_size == 0 {
+            return Ok(());
+        }
+
+        let previous_passwords = password_history::Entity::find()
+            .filter(password_history::Column::UserId.eq(user.id))
+            .order_by_desc(password_history::Column::Id)
+            .limit(app_config.password_history_size - 1)
+            .all(db)
+            .await?;
+
+        let password_hasher = PasswordHasher::new(app_config)?;
+
+        let password_hashes = std::iter::once(user.password.as_str())
+            .chain(
+                previous_passwords
+                    .iter()
+                    .map(|entry| entry.password_hash.as_str()),
+            )
+            .filter(|password_hash| !password_hash.is_empty());
+
+        for password_hash in password_hashes {
+            if password_hasher.verify(password_hash, password).await?
+                != PasswordVerification::Invalid
+            {
+                return Err(password_error(garde::Error::new(
+                    "Password was used recently, choose a different one.",
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records the user's current password before it gets replaced, forgetting passwords that
+    /// fell out of the history.
+    pub async fn record<C: ConnectionTrait>(
+        db: &C,
+        app_config: &AppConfig,
+        user: &user::Model,
+    ) -> Result<(), AppError> {
+        // the current password is checked from the user itself
+        let kept_entries = app_config.password_history_size.saturating_sub(1);
+
+        if kept_entries == 0 || user.password.is_empty() {
+            return Ok(());
+        }
+
+        password_history::ActiveModel {
+            id: NotSet,
+            user_id: Set(user.id),
+            password_hash: Set(user.password.clone()),
+            date_created: Set(Utc::now().naive_utc()),
+        }
+        .insert(db)
+        .await?;
+
+        let expired_ids: Vec<i32> = password_history::Entity::find()
+            .filter(password_history::Column::UserId.eq(user.id))
+            .order_by_desc(password_history::Column::Id)
+            .all(db)
+            .await?
+            .into_iter()
+            .skip(kept_entries as usize)
+            .map(|entry| entry.id)
+            .collect();
+
+        if !expired_ids.is_empty() {
+            password_history::Entity::delete_many()
+                .filter(password_history::Column::Id.is_in(expired_ids))
+                .exec(db)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn password_error(error: garde::Error) -> AppError {
+    let mut report = garde::Report::new();
+    report.append(garde::Path::new("password"), error);
+
+    AppError::GardeValidation(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            max_length: 16,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: false,
+            blocklist: HashSet::from(["passw0rd1a".to_string()]),
+        }
+    }
+
+    #[test]
+    fn test_password_policy_rules() {
+        let policy = policy();
+        let identifiers = ["anish", "kumar.dev@example.com"];
+
+        assert!(policy.check("Correct7horse", &identifiers).is_ok());
+
+        assert!(policy.check("Short7", &identifiers).is_err());
+        assert!(
+            policy
+                .check("Much7too7long7password", &identifiers)
+                .is_err()
+        );
+        assert!(policy.check("correct7horse", &identifiers).is_err());
+        assert!(policy.check("Correcthorse", &identifiers).is_err());
+        assert!(policy.check("Horse7ANISH", &identifiers).is_err());
+        assert!(policy.check("Kumar.DEV7x", &identifiers).is_err());
+        assert!(policy.check("Passw0rd1A", &identifiers).is_err());
+    }
+}
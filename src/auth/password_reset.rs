@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore as _};
+use chrono::{Duration, Utc};
+use sea_orm::{
+    ActiveModelTrait as _, ActiveValue::NotSet, ColumnTrait as _, ConnectionTrait,
+    EntityTrait as _, QueryFilter as _, Set, TransactionTrait as _, sea_query::Expr,
+};
+use sha2::{Digest as _, Sha256};
+
+use crate::{
+    AppState,
+    auth::{password::PasswordHasher, password_policy::PasswordHistory, token_store::TokenStore},
+    configgg::AppConfig,
+    error::AppError,
+    models::_entities::{password_reset_token, user},
+};
+
+/// Issues and redeems single-use password reset tokens.
+///
+/// Only the SHA-256 digest of a token is stored, the token itself is only ever sent by email.
+pub struct PasswordReset;
+
+impl PasswordReset {
+    /// Creates a reset token for the user, replacing any earlier unused token.
+    pub async fn create_token<C: ConnectionTrait>(
+        db: &C,
+        app_config: &AppConfig,
+        user_id: i32,
+    ) -> Result<String, AppError> {
+        let mut token_bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut token_bytes);
+        let token = hex::encode(token_bytes);
+
+        password_reset_token::Entity::delete_many()
+            .filter(password_reset_token::Column::UserId.eq(user_id))
+            .exec(db)
+            .await?;
+
+        let now = Utc::now();
+        let expires_at = now + Duration::minutes(app_config.password_reset_expiration_minutes);
+
+        password_reset_token::ActiveModel {
+            id: NotSet,
+            user_id: Set(user_id),
+            token_hash: Set(hash_token(&token)),
+            expires_at: Set(expires_at.naive_utc()),
+            used_at: Set(None),
+            date_created: Set(now.naive_utc()),
+        }
+        .insert(db)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Consumes the token, sets the new password, lifts a login lockout and revokes every token
+    /// issued to the user.
+    pub async fn reset_password(
+        ctx: &Arc<AppState>,
+        token: &str,
+        password: &str,
+    ) -> Result<user::Model, AppError> {
+        let token_hash = hash_token(token);
+        let password_hash = PasswordHasher::new(&ctx.config)?.hash(password).await?;
+        let now = Utc::now().naive_utc();
+
+        let txn = ctx.db.begin().await?;
+
+        // marking the token as used first makes concurrent redemptions of the same token fail
+        let res = password_reset_token::Entity::update_many()
+            .col_expr(password_reset_token::Column::UsedAt, Expr::value(now))
+            .filter(password_reset_token::Column::TokenHash.eq(&token_hash))
+            .filter(password_reset_token::Column::UsedAt.is_null())
+            .filter(password_reset_token::Column::ExpiresAt.gt(now))
+            .exec(&txn)
+            .await?;
+
+        if res.rows_affected == 0 {
+            return Err(AppError::InvalidToken);
+        }
+
+        let reset_token = password_reset_token::Entity::find()
+            .filter(password_reset_token::Column::TokenHash.eq(&token_hash))
+            .one(&txn)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+
+        let user = user::Entity::find_by_id(reset_token.user_id)
+            .one(&txn)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+
+        // dropping the transaction on a rejected password leaves the token unused
+        ctx.password_policy
+            .validate(password, &[&user.username, &user.email])?;
+        PasswordHistory::ensure_not_reused(&txn, &ctx.config, &user, password).await?;
+        PasswordHistory::record(&txn, &ctx.config, &user).await?;
+
+        // receiving the reset email proves ownership of the address
+        let email_verified_at = user.email_verified_at.unwrap_or(now);
+
+        let mut user: user::ActiveModel = user.into();
+        user.password = Set(password_hash);
+        user.email_verified_at = Set(Some(email_verified_at));
+        user.failed_login_attempts = Set(0);
+        user.last_failed_login_at = Set(None);
+        user.locked_until = Set(None);
+        let user = user.update(&txn).await?;
+
+        TokenStore::revoke_all_for_user(&txn, user.id).await?;
+
+        txn.commit().await?;
+
+        Ok(user)
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// Permission code names and role names a user holds, directly or through their roles.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserGrants {
+    pub permissions: Vec<String>,
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PermissionCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub ttl_seconds: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    grants: HashMap<i32, (Instant, Arc<UserGrants>)>,
+    /// Bumped by every invalidation, so grants loaded before one aren't stored after it.
+    generation: u64,
+}
+
+/// In-memory cache of each user's resolved grants, kept for `permission_cache_ttl_seconds`.
+///
+/// Entries are invalidated whenever the permissions or roles they were resolved from change,
+/// the TTL only bounds how stale they get when a change bypasses the API.
+#[derive(Debug)]
+pub struct PermissionCache {
+    ttl: Duration,
+    entries: Mutex<CacheEntries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl PermissionCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, user_id: i32) -> Option<Arc<UserGrants>> {
+        let now = Instant::now();
+        let entries = self.entries.lock().unwrap();
+
+        let grants = entries
+            .grants
+            .get(&user_id)
+            .filter(|(expires_at, _)| *expires_at > now)
+            .map(|(_, grants)| grants.clone());
+
+        let counter = match grants {
+            Some(_) => &self.hits,
+            None => &self.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        grants
+    }
+
+    /// Current generation, to read before loading grants that are then passed to `insert`.
+    pub fn generation(&self) -> u64 {
+        self.entries.lock().unwrap().generation
+    }
+
+    /// Stores grants loaded at `generation`, unless they were invalidated meanwhile.
+    pub fn insert(&self, user_id: i32, grants: Arc<UserGrants>, generation: u64) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.generation != generation {
+            return;
+        }
+
+        entries
+            .grants
+            .retain(|_, (expires_at, _)| *expires_at > now);
+        entries.grants.insert(user_id, (now + self.ttl, grants));
+    }
+
+    /// Forgets the grants of a user whose permissions or roles changed.
+    pub fn invalidate_user(&self, user_id: i32) {
+        let mut entries = self.entries.lock().unwrap();
+
+        entries.generation += 1;
+        entries.grants.remove(&user_id);
+    }
+
+    /// Forgets every user's grants, after a change to a role or permission any of them may hold.
+    pub fn invalidate_all(&self) {
+        let mut entries = self.entries.lock().unwrap();
+
+        entries.generation += 1;
+        entries.grants.clear();
+    }
+
+    pub fn stats(&self) -> PermissionCacheStats {
+        PermissionCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().grants.len(),
+            ttl_seconds: self.ttl.as_secs(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grants(permission: &str) -> Arc<UserGrants> {
+        Arc::new(UserGrants {
+            permissions: vec![permission.to_string()],
+            roles: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn test_permission_cache_invalidation() {
+        let cache = PermissionCache::new(Duration::from_secs(60));
+
+        assert_eq!(cache.get(1), None);
+
+        cache.insert(1, grants("read_users"), cache.generation());
+        cache.insert(2, grants("read_roles"), cache.generation());
+        assert_eq!(cache.get(1), Some(grants("read_users")));
+
+        cache.invalidate_user(1);
+        assert_eq!(cache.get(1), None);
+        assert_eq!(cache.get(2), Some(grants("read_roles")));
+
+        // grants loaded before an invalidation are dropped
+        let generation = cache.generation();
+        cache.invalidate_all();
+        cache.insert(1, grants("read_users"), generation);
+        assert_eq!(cache.get(1), None);
+        assert_eq!(cache.get(2), None);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 4, 0));
+    }
+
+    #[test]
+    fn test_permission_cache_expiry() {
+        let cache = PermissionCache::new(Duration::ZERO);
+
+        cache.insert(1, grants("read_users"), cache.generation());
+        assert_eq!(cache.get(1), None);
+    }
+}
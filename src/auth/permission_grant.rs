@@ -0,0 +1,67 @@
+use sea_orm::{ColumnTrait as _, Condition};
+
+use crate::models::_entities::permission;
+
+/// Separates the segments of hierarchical code names such as `users.read`.
+const SEGMENT_SEPARATOR: char = '.';
+
+/// Matches any single segment, or every remaining segment at the end of a grant.
+const WILDCARD: &str = "*";
+
+/// Whether a granted permission code name covers the required one.
+///
+/// Code names match exactly, except for `*` segments in the grant: `*.read` covers `users.read`
+/// and `roles.read`, a trailing `*` covers everything below it, so `users.*` covers
+/// `users.read` and `users.sessions.delete`, and a lone `*` covers every permission.
+pub fn grants(granted: &str, required: &str) -> bool {
+    if !granted.contains(WILDCARD) {
+        return granted == required;
+    }
+
+    let granted: Vec<&str> = granted.split(SEGMENT_SEPARATOR).collect();
+    let required: Vec<&str> = required.split(SEGMENT_SEPARATOR).collect();
+
+    let Some((last, leading)) = granted.split_last() else {
+        return false;
+    };
+
+    let segment_matches =
+        |(granted, required): (&&str, &&str)| *granted == WILDCARD || granted == required;
+
+    if *last == WILDCARD {
+        required.len() > leading.len() && leading.iter().zip(required.iter()).all(segment_matches)
+    } else {
+        granted.len() == required.len() && granted.iter().zip(required.iter()).all(segment_matches)
+    }
+}
+
+/// Narrows permissions down to the ones that may grant `required`, for `grants` to decide on.
+pub fn candidates(required: &str) -> Condition {
+    Condition::any()
+        .add(permission::Column::CodeName.eq(required))
+        .add(permission::Column::CodeName.contains(WILDCARD))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_grants() {
+        assert!(grants("read_users", "read_users"));
+        assert!(!grants("read_users_admin", "read_user"));
+        assert!(!grants("read_user", "read_users"));
+
+        assert!(grants("users.*", "users.read"));
+        assert!(grants("users.*", "users.sessions.delete"));
+        assert!(!grants("users.*", "users"));
+        assert!(!grants("users.*", "roles.read"));
+
+        assert!(grants("*.read", "users.read"));
+        assert!(!grants("*.read", "users.write"));
+        assert!(!grants("*.read", "users.sessions.read"));
+
+        assert!(grants("*", "users.read"));
+        assert!(grants("*", "read_users"));
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::{HashMap, HashSet};
+
+use sea_orm::{
+    ColumnTrait as _, ConnectionTrait, EntityTrait as _, QueryFilter as _, QuerySelect as _,
+};
+
+use crate::{error::AppError, models::_entities::role};
+
+/// Parent links between roles. A role inherits every permission of its parent, and through it
+/// those of the parent's own parent, so `admin` → `editor` → `viewer` gives admins everything
+/// editors and viewers hold.
+///
+/// Links are checked with `would_cycle` before they are written, the walks still stop at a role
+/// they've already visited.
+#[derive(Debug, Default)]
+pub struct RoleHierarchy {
+    parents: HashMap<i32, i32>,
+}
+
+impl RoleHierarchy {
+    pub async fn load<C: ConnectionTrait>(db: &C) -> Result<Self, AppError> {
+        let parents = role::Entity::find()
+            .select_only()
+            .column(role::Column::Id)
+            .column(role::Column::ParentId)
+            .filter(role::Column::ParentId.is_not_null())
+            .into_tuple::<(i32, i32)>()
+            .all(db)
+            .await?
+            .into_iter()
+            .collect();
+
+        Ok(Self { parents })
+    }
+
+    /// The roles followed by every role they inherit from, nearest first and without duplicates.
+    pub fn inherited(&self, role_ids: impl IntoIterator<Item = i32>) -> Vec<i32> {
+        let mut visited = HashSet::new();
+        let mut inherited = Vec::new();
+
+        for role_id in role_ids {
+            let mut next = Some(role_id);
+
+            while let Some(role_id) = next.filter(|role_id| visited.insert(*role_id)) {
+                inherited.push(role_id);
+                next = self.parents.get(&role_id).copied();
+            }
+        }
+
+        inherited
+    }
+
+    /// The role followed by every role inheriting from it, which lose what it loses.
+    pub fn inheriting(&self, role_id: i32) -> Vec<i32> {
+        let mut inheriting: Vec<i32> = self
+            .parents
+            .keys()
+            .copied()
+            .filter(|id| *id != role_id && self.inherited([*id]).contains(&role_id))
+            .collect();
+
+        inheriting.sort_unstable();
+        inheriting.insert(0, role_id);
+
+        inheriting
+    }
+
+    /// Whether making `parent_id` the parent of `role_id` would let the role inherit from itself.
+    pub fn would_cycle(&self, role_id: i32, parent_id: i32) -> bool {
+        self.inherited([parent_id]).contains(&role_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_hierarchy() {
+        // admin (1) → editor (2) → viewer (3), auditor (4) → viewer (3)
+        let hierarchy = RoleHierarchy {
+            parents: HashMap::from([(1, 2), (2, 3), (4, 3)]),
+        };
+
+        assert_eq!(hierarchy.inherited([1]), vec![1, 2, 3]);
+        assert_eq!(hierarchy.inherited([4, 2]), vec![4, 3, 2]);
+        assert_eq!(hierarchy.inherited([5]), vec![5]);
+
+        assert_eq!(hierarchy.inheriting(3), vec![3, 1, 2, 4]);
+        assert_eq!(hierarchy.inheriting(2), vec![2, 1]);
+        assert_eq!(hierarchy.inheriting(5), vec![5]);
+
+        assert!(hierarchy.would_cycle(3, 1));
+        assert!(hierarchy.would_cycle(2, 2));
+        assert!(!hierarchy.would_cycle(1, 4));
+        assert!(!hierarchy.would_cycle(4, 2));
+    }
+}
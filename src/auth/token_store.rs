@@ -0,0 +1,398 @@
+use std::{net::IpAddr, sync::Arc};
+
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait as _,
+    ActiveValue::NotSet,
+    ColumnTrait as _, Condition, ConnectionTrait, DbErr, EntityTrait as _, PaginatorTrait as _,
+    QueryFilter as _, QueryOrder as _, Set,
+    sea_query::{Expr, OnConflict, Query},
+};
+
+use crate::{
+    AppState,
+    auth::{
+        deactivation::Deactivation,
+        jwt::{TokenClaims, TokenType, UserToken},
+    },
+    configgg::AppConfig,
+    error::AppError,
+    models::_entities::{revoked_token, token_family, user, user_role},
+};
+
+/// Where a login came from, recorded on the session it starts.
+#[derive(Debug, Clone, Default)]
+pub struct SessionDevice {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<IpAddr>,
+}
+
+/// Server-side store of issued and revoked tokens.
+///
+/// A revocation row revokes a single token by its `jti`. Every token of a user is revoked at
+/// once by bumping their token version, which tokens carry in their `ver` claim.
+///
+/// Every login starts a token family, which is what users see as a session. Refreshing rotates
+/// the family's refresh token, and presenting an already rotated refresh token revokes the whole
+/// family.
+pub struct TokenStore;
+
+impl TokenStore {
+    /// Starts a new token family for the user and issues its first token pair.
+    pub async fn issue_token_pair(
+        ctx: &Arc<AppState>,
+        user: &user::Model,
+        device: SessionDevice,
+    ) -> Result<UserToken, AppError> {
+        let family = token_family::ActiveModel {
+            id: NotSet,
+            user_id: Set(user.id),
+            refresh_jti: Set(String::new()),
+            revoked_at: Set(None),
+            date_created: Set(Utc::now().naive_utc()),
+            user_agent: Set(device.user_agent),
+            ip_address: Set(device.ip_address.map(|ip_address| ip_address.to_string())),
+            last_refreshed_at: Set(None),
+        }
+        .insert(&ctx.db)
+        .await?;
+
+        let (user_token, refresh_jti) = create_token_pair(ctx, user, family.id)?;
+
+        token_family::Entity::update_many()
+            .col_expr(token_family::Column::RefreshJti, Expr::value(refresh_jti))
+            .filter(token_family::Column::Id.eq(family.id))
+            .exec(&ctx.db)
+            .await?;
+
+        Ok(user_token)
+    }
+
+    /// Exchanges a refresh token for a new token pair of the same family.
+    pub async fn rotate(
+        ctx: &Arc<AppState>,
+        refresh_claims: &TokenClaims,
+        user: &user::Model,
+    ) -> Result<UserToken, AppError> {
+        let family_id = refresh_claims.fam.ok_or(AppError::InvalidToken)?;
+
+        let (user_token, refresh_jti) = create_token_pair(ctx, user, family_id)?;
+
+        // only the current refresh token of a live family can be swapped
+        let res = token_family::Entity::update_many()
+            .col_expr(token_family::Column::RefreshJti, Expr::value(refresh_jti))
+            .col_expr(
+                token_family::Column::LastRefreshedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(token_family::Column::Id.eq(family_id))
+            .filter(token_family::Column::UserId.eq(user.id))
+            .filter(token_family::Column::RefreshJti.eq(&refresh_claims.jti))
+            .filter(token_family::Column::RevokedAt.is_null())
+            .exec(&ctx.db)
+            .await?;
+
+        if res.rows_affected == 0 {
+            tracing::warn!(
+                user_id = user.id,
+                family_id,
+                jti = refresh_claims.jti,
+                "Refresh token reuse detected, revoking token family"
+            );
+
+            Self::revoke_family(&ctx.db, family_id).await?;
+
+            return Err(AppError::InvalidToken);
+        }
+
+        Ok(user_token)
+    }
+
+    pub async fn revoke_family<C: ConnectionTrait>(db: &C, family_id: i32) -> Result<(), AppError> {
+        token_family::Entity::update_many()
+            .col_expr(
+                token_family::Column::RevokedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(token_family::Column::Id.eq(family_id))
+            .filter(token_family::Column::RevokedAt.is_null())
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Sessions of the user that are neither revoked nor past the refresh token lifetime.
+    pub async fn active_sessions<C: ConnectionTrait>(
+        db: &C,
+        app_config: &AppConfig,
+        user_id: i32,
+    ) -> Result<Vec<token_family::Model>, AppError> {
+        let cutoff = (Utc::now() - Duration::minutes(app_config.refresh_token_expiration_minutes))
+            .naive_utc();
+
+        let sessions = token_family::Entity::find()
+            .filter(token_family::Column::UserId.eq(user_id))
+            .filter(token_family::Column::RevokedAt.is_null())
+            .filter(
+                Condition::any()
+                    .add(token_family::Column::DateCreated.gt(cutoff))
+                    .add(token_family::Column::LastRefreshedAt.gt(cutoff)),
+            )
+            .order_by_desc(token_family::Column::DateCreated)
+            .all(db)
+            .await?;
+
+        Ok(sessions)
+    }
+
+    /// Signs the user out of one session, invalidating its access and refresh tokens.
+    pub async fn revoke_session<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+        session_id: i32,
+    ) -> Result<(), AppError> {
+        let res = token_family::Entity::update_many()
+            .col_expr(
+                token_family::Column::RevokedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(token_family::Column::Id.eq(session_id))
+            .filter(token_family::Column::UserId.eq(user_id))
+            .filter(token_family::Column::RevokedAt.is_null())
+            .exec(db)
+            .await?;
+
+        if res.rows_affected == 0 {
+            return Err(DbErr::RecordNotFound("Session not found.".to_string()).into());
+        }
+
+        Ok(())
+    }
+
+    pub async fn revoke<C: ConnectionTrait>(
+        db: &C,
+        token_claims: &TokenClaims,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        Self::purge_expired(db).await?;
+
+        let already_revoked = revoked_token::Entity::find()
+            .filter(revoked_token::Column::Jti.eq(&token_claims.jti))
+            .count(db)
+            .await?;
+
+        if already_revoked > 0 {
+            return Ok(());
+        }
+
+        revoked_token::ActiveModel {
+            id: NotSet,
+            jti: Set(Some(token_claims.jti.clone())),
+            user_id: Set(user_id),
+            expires_at: Set(timestamp_to_datetime(token_claims.exp)),
+            date_created: Set(Utc::now().naive_utc()),
+        }
+        .insert(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Revokes a single-use token, failing with `InvalidToken` if it has been used already.
+    ///
+    /// The unique `jti` makes the insert itself the check, so of two concurrent redemptions only
+    /// one gets through.
+    pub async fn redeem<C: ConnectionTrait>(
+        db: &C,
+        token_claims: &TokenClaims,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        Self::purge_expired(db).await?;
+
+        let inserted = revoked_token::Entity::insert(revoked_token::ActiveModel {
+            id: NotSet,
+            jti: Set(Some(token_claims.jti.clone())),
+            user_id: Set(user_id),
+            expires_at: Set(timestamp_to_datetime(token_claims.exp)),
+            date_created: Set(Utc::now().naive_utc()),
+        })
+        .on_conflict(
+            OnConflict::column(revoked_token::Column::Jti)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+
+        if inserted == 0 {
+            return Err(AppError::InvalidToken);
+        }
+
+        Ok(())
+    }
+
+    /// Revokes every token issued to the user so far, e.g. after a password change, and ends
+    /// their sessions.
+    pub async fn revoke_all_for_user<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        token_family::Entity::update_many()
+            .col_expr(
+                token_family::Column::RevokedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(token_family::Column::UserId.eq(user_id))
+            .filter(token_family::Column::RevokedAt.is_null())
+            .exec(db)
+            .await?;
+
+        Self::bump_token_version(db, user_id).await
+    }
+
+    /// Invalidates every token issued to the user so far, e.g. after their roles change.
+    pub async fn bump_token_version<C: ConnectionTrait>(
+        db: &C,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        user::Entity::update_many()
+            .col_expr(
+                user::Column::TokenVersion,
+                Expr::col(user::Column::TokenVersion).add(1),
+            )
+            .filter(user::Column::Id.eq(user_id))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Invalidates every token issued to members of the roles, e.g. after the roles lose
+    /// permissions.
+    pub async fn bump_token_version_for_roles<C: ConnectionTrait>(
+        db: &C,
+        role_ids: Vec<i32>,
+    ) -> Result<(), AppError> {
+        user::Entity::update_many()
+            .col_expr(
+                user::Column::TokenVersion,
+                Expr::col(user::Column::TokenVersion).add(1),
+            )
+            .filter(
+                user::Column::Id.in_subquery(
+                    Query::select()
+                        .column(user_role::Column::UserId)
+                        .from(user_role::Entity)
+                        .and_where(user_role::Column::RoleId.is_in(role_ids))
+                        .to_owned(),
+                ),
+            )
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Resolves the user a token was issued to, failing if the token is no longer valid.
+    pub async fn authenticate<C: ConnectionTrait>(
+        db: &C,
+        token_claims: &TokenClaims,
+    ) -> Result<user::Model, AppError> {
+        let user = user::Entity::find_by_id(token_claims.user_id()?)
+            .one(db)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+
+        if token_claims.ver != user.token_version
+            || Self::is_revoked(db, token_claims, user.id).await?
+        {
+            return Err(AppError::InvalidToken);
+        }
+
+        Deactivation::ensure_active(&user)?;
+
+        Ok(user)
+    }
+
+    pub async fn is_revoked<C: ConnectionTrait>(
+        db: &C,
+        token_claims: &TokenClaims,
+        user_id: i32,
+    ) -> Result<bool, AppError> {
+        let count = revoked_token::Entity::find()
+            .filter(revoked_token::Column::Jti.eq(&token_claims.jti))
+            .count(db)
+            .await?;
+
+        if count > 0 {
+            return Ok(true);
+        }
+
+        let Some(family_id) = token_claims.fam else {
+            return Ok(false);
+        };
+
+        // a family that no longer exists is treated as revoked
+        let live_family = token_family::Entity::find_by_id(family_id)
+            .filter(token_family::Column::UserId.eq(user_id))
+            .filter(token_family::Column::RevokedAt.is_null())
+            .count(db)
+            .await?;
+
+        Ok(live_family == 0)
+    }
+
+    /// Removes revocations whose tokens would have expired anyway.
+    async fn purge_expired<C: ConnectionTrait>(db: &C) -> Result<(), AppError> {
+        revoked_token::Entity::delete_many()
+            .filter(revoked_token::Column::ExpiresAt.lt(Utc::now().naive_utc()))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Returns the encoded token pair along with the `jti` of the refresh token.
+fn create_token_pair(
+    ctx: &Arc<AppState>,
+    user: &user::Model,
+    family_id: i32,
+) -> Result<(UserToken, String), AppError> {
+    let access_claims = TokenClaims::for_user(
+        user,
+        TokenType::Access,
+        ctx.config.access_token_expiration_minutes,
+    )
+    .with_family(family_id);
+
+    let refresh_claims = TokenClaims::for_user(
+        user,
+        TokenType::Refresh,
+        ctx.config.refresh_token_expiration_minutes,
+    )
+    .with_family(family_id);
+
+    let access_token = ctx
+        .jwt_keys
+        .encode(&access_claims)
+        .map_err(AppError::GenericError)?;
+    let refresh_token = ctx
+        .jwt_keys
+        .encode(&refresh_claims)
+        .map_err(AppError::GenericError)?;
+
+    let user_token = UserToken {
+        access_token,
+        refresh_token: Some(refresh_token),
+    };
+
+    Ok((user_token, refresh_claims.jti))
+}
+
+fn timestamp_to_datetime(timestamp: usize) -> NaiveDateTime {
+    DateTime::from_timestamp(timestamp as i64, 0)
+        .unwrap_or_default()
+        .naive_utc()
+}
@@ -0,0 +1,230 @@
+use std::sync::Arc;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore as _};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait as _, ActiveValue::NotSet, ColumnTrait as _, Condition, ConnectionTrait,
+    EntityTrait as _, QueryFilter as _, Set, TransactionTrait as _, sea_query::Expr,
+};
+use serde::Serialize;
+use sha2::{Digest as _, Sha256};
+
+use crate::{
+    AppState,
+    auth::{
+        jwt::{TokenClaims, TokenType},
+        totp::Totp,
+    },
+    error::AppError,
+    models::_entities::{recovery_code, user},
+};
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+    pub recovery_codes: Vec<String>,
+}
+
+/// TOTP based two-factor authentication.
+///
+/// Enrolling stores a new secret and recovery codes, but 2FA is only enforced once the user
+/// confirms the enrollment with a first code. Recovery codes are stored hashed and can each be
+/// used once in place of a TOTP code.
+pub struct TwoFactor;
+
+impl TwoFactor {
+    pub async fn enroll(
+        ctx: &Arc<AppState>,
+        user: &user::Model,
+    ) -> Result<TwoFactorEnrollment, AppError> {
+        if user.totp_enabled_at.is_some() {
+            return Err(AppError::GenericError(
+                "Two-factor authentication is already enabled.".to_string(),
+            ));
+        }
+
+        let totp = Totp::generate();
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect();
+
+        let txn = ctx.db.begin().await?;
+
+        let mut active_user: user::ActiveModel = user.clone().into();
+        active_user.totp_secret = Set(Some(totp.to_base32()));
+        active_user.totp_last_used_step = Set(None);
+        active_user.update(&txn).await?;
+
+        recovery_code::Entity::delete_many()
+            .filter(recovery_code::Column::UserId.eq(user.id))
+            .exec(&txn)
+            .await?;
+
+        let now = Utc::now().naive_utc();
+
+        recovery_code::Entity::insert_many(recovery_codes.iter().map(|code| {
+            recovery_code::ActiveModel {
+                id: NotSet,
+                user_id: Set(user.id),
+                code_hash: Set(hash_recovery_code(code)),
+                used_at: Set(None),
+                date_created: Set(now),
+            }
+        }))
+        .exec(&txn)
+        .await?;
+
+        txn.commit().await?;
+
+        Ok(TwoFactorEnrollment {
+            secret: totp.to_base32(),
+            otpauth_uri: totp.otpauth_uri(&ctx.config.two_factor_issuer, &user.email),
+            recovery_codes,
+        })
+    }
+
+    /// Enables 2FA once the user proves their authenticator produces valid codes.
+    pub async fn confirm<C: ConnectionTrait>(
+        db: &C,
+        user: &user::Model,
+        code: &str,
+    ) -> Result<user::Model, AppError> {
+        if user.totp_enabled_at.is_some() {
+            return Err(AppError::GenericError(
+                "Two-factor authentication is already enabled.".to_string(),
+            ));
+        }
+
+        if user.totp_secret.is_none() {
+            return Err(AppError::GenericError(
+                "Two-factor authentication is not enrolled.".to_string(),
+            ));
+        }
+
+        if !Self::verify_totp(db, user, code).await? {
+            return Err(AppError::InvalidTwoFactorCode);
+        }
+
+        let mut user: user::ActiveModel = user.clone().into();
+        user.totp_enabled_at = Set(Some(Utc::now().naive_utc()));
+
+        Ok(user.update(db).await?)
+    }
+
+    pub async fn disable<C: ConnectionTrait>(
+        db: &C,
+        user: &user::Model,
+        code: &str,
+    ) -> Result<(), AppError> {
+        if user.totp_enabled_at.is_none() {
+            return Err(AppError::GenericError(
+                "Two-factor authentication is not enabled.".to_string(),
+            ));
+        }
+
+        if !Self::verify_code(db, user, code).await? {
+            return Err(AppError::InvalidTwoFactorCode);
+        }
+
+        let mut active_user: user::ActiveModel = user.clone().into();
+        active_user.totp_secret = Set(None);
+        active_user.totp_enabled_at = Set(None);
+        active_user.totp_last_used_step = Set(None);
+        active_user.update(db).await?;
+
+        recovery_code::Entity::delete_many()
+            .filter(recovery_code::Column::UserId.eq(user.id))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Short-lived token returned by `login` in place of a token pair when 2FA is enabled.
+    pub fn challenge_token(ctx: &Arc<AppState>, user: &user::Model) -> Result<String, AppError> {
+        let token_claims = TokenClaims::for_user(
+            user,
+            TokenType::TwoFactorChallenge,
+            ctx.config.two_factor_challenge_expiration_minutes,
+        );
+
+        ctx.jwt_keys
+            .encode(&token_claims)
+            .map_err(AppError::GenericError)
+    }
+
+    /// Accepts either a current TOTP code or an unused recovery code.
+    pub async fn verify_code<C: ConnectionTrait>(
+        db: &C,
+        user: &user::Model,
+        code: &str,
+    ) -> Result<bool, AppError> {
+        if Self::verify_totp(db, user, code).await? {
+            return Ok(true);
+        }
+
+        let res = recovery_code::Entity::update_many()
+            .col_expr(
+                recovery_code::Column::UsedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(recovery_code::Column::UserId.eq(user.id))
+            .filter(recovery_code::Column::CodeHash.eq(hash_recovery_code(code)))
+            .filter(recovery_code::Column::UsedAt.is_null())
+            .exec(db)
+            .await?;
+
+        Ok(res.rows_affected > 0)
+    }
+
+    /// A TOTP code is accepted at most once, so an intercepted code can't be replayed.
+    async fn verify_totp<C: ConnectionTrait>(
+        db: &C,
+        user: &user::Model,
+        code: &str,
+    ) -> Result<bool, AppError> {
+        let Some(secret) = &user.totp_secret else {
+            return Ok(false);
+        };
+
+        let Some(step) = Totp::from_base32(secret)?.verify(code.trim(), Utc::now().timestamp())
+        else {
+            return Ok(false);
+        };
+
+        let res = user::Entity::update_many()
+            .col_expr(user::Column::TotpLastUsedStep, Expr::value(step))
+            .filter(user::Column::Id.eq(user.id))
+            .filter(
+                Condition::any()
+                    .add(user::Column::TotpLastUsedStep.is_null())
+                    .add(user::Column::TotpLastUsedStep.lt(step)),
+            )
+            .exec(db)
+            .await?;
+
+        Ok(res.rows_affected > 0)
+    }
+}
+
+/// Ten random hex digits, grouped as `xxxxx-xxxxx`.
+fn generate_recovery_code() -> String {
+    let mut code_bytes = [0u8; 5];
+    OsRng.fill_bytes(&mut code_bytes);
+    let code = hex::encode(code_bytes);
+
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
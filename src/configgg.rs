@@ -1,14 +1,114 @@
+use jsonwebtoken::Algorithm;
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
     pub app_debug: bool,
     pub server_address: String,
+    /// Public base URL of the API, used to build links sent by email.
+    #[serde(default = "default_app_url")]
+    pub app_url: String,
     pub database_url: String,
     pub per_page: i32,
     pub jwt_secret: String,
+    #[serde(default)]
+    pub jwt_algorithm: Algorithm,
+    #[serde(default)]
+    pub jwt_signing_key_id: Option<String>,
+    #[serde(default)]
+    pub jwt_private_key_path: Option<String>,
+    /// Comma separated `kid=path` pairs of PEM public keys accepted for verification.
+    #[serde(default)]
+    pub jwt_public_keys: Option<String>,
+    #[serde(default)]
+    pub jwt_issuer: Option<String>,
+    #[serde(default)]
+    pub jwt_audience: Option<String>,
+    #[serde(default = "default_jwt_leeway_seconds")]
+    pub jwt_leeway_seconds: u64,
     pub access_token_expiration_minutes: i64,
     pub refresh_token_expiration_minutes: i64,
+    #[serde(default = "default_argon2_memory_kib")]
+    pub argon2_memory_kib: u32,
+    #[serde(default = "default_argon2_iterations")]
+    pub argon2_iterations: u32,
+    #[serde(default = "default_argon2_parallelism")]
+    pub argon2_parallelism: u32,
+    #[serde(default = "default_password_min_length")]
+    pub password_min_length: usize,
+    #[serde(default = "default_password_max_length")]
+    pub password_max_length: usize,
+    #[serde(default)]
+    pub password_require_lowercase: bool,
+    #[serde(default)]
+    pub password_require_uppercase: bool,
+    #[serde(default)]
+    pub password_require_digit: bool,
+    #[serde(default)]
+    pub password_require_symbol: bool,
+    /// File of breached or common passwords, one per line, that are refused.
+    #[serde(default)]
+    pub password_blocklist_path: Option<String>,
+    /// Number of most recent passwords, the current one included, a user can't reuse.
+    #[serde(default = "default_password_history_size")]
+    pub password_history_size: u64,
+    /// Refuses login and protected routes until the user has verified their email address.
+    #[serde(default)]
+    pub require_email_verification: bool,
+    #[serde(default = "default_email_verification_expiration_minutes")]
+    pub email_verification_expiration_minutes: i64,
+    #[serde(default = "default_password_reset_expiration_minutes")]
+    pub password_reset_expiration_minutes: i64,
+    /// Issuer shown by authenticator apps next to the account name.
+    #[serde(default = "default_two_factor_issuer")]
+    pub two_factor_issuer: String,
+    #[serde(default = "default_two_factor_challenge_expiration_minutes")]
+    pub two_factor_challenge_expiration_minutes: i64,
+    /// Failed logins of an account before it gets locked.
+    #[serde(default = "default_login_max_attempts")]
+    pub login_max_attempts: u32,
+    /// Failed logins from a client IP before it gets locked.
+    #[serde(default = "default_login_ip_max_attempts")]
+    pub login_ip_max_attempts: u32,
+    /// First lockout duration, doubled by every further failed attempt.
+    #[serde(default = "default_login_lockout_base_seconds")]
+    pub login_lockout_base_seconds: i64,
+    #[serde(default = "default_login_lockout_max_seconds")]
+    pub login_lockout_max_seconds: i64,
+    /// Failed attempts are forgotten after this long without a new failure.
+    #[serde(default = "default_login_attempt_window_seconds")]
+    pub login_attempt_window_seconds: i64,
+    /// Take the client IP from `X-Forwarded-For`. Only enable behind a reverse proxy that sets it.
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+    /// JSON array of OpenID Connect providers, each with a `name`, `issuer`, `client_id`,
+    /// `client_secret` and optional `scopes`.
+    #[serde(default)]
+    pub oauth_providers: Option<String>,
+    /// How long a user has to complete a sign in with an external provider.
+    #[serde(default = "default_oauth_state_expiration_minutes")]
+    pub oauth_state_expiration_minutes: i64,
+    /// Sets issued tokens as HttpOnly cookies instead of returning them in the response body.
+    #[serde(default)]
+    pub auth_cookies: bool,
+    #[serde(default = "default_auth_cookie_secure")]
+    pub auth_cookie_secure: bool,
+    /// `Strict`, `Lax` or `None`.
+    #[serde(default = "default_auth_cookie_same_site")]
+    pub auth_cookie_same_site: String,
+    #[serde(default)]
+    pub auth_cookie_domain: Option<String>,
+    /// Lets users sign in with a link emailed to them instead of their password.
+    #[serde(default)]
+    pub magic_link_login: bool,
+    #[serde(default = "default_magic_link_expiration_minutes")]
+    pub magic_link_expiration_minutes: i64,
+    /// Lifetime of impersonation tokens, which can't be refreshed.
+    #[serde(default = "default_impersonation_expiration_minutes")]
+    pub impersonation_expiration_minutes: i64,
+    /// How long a user's resolved permissions and roles are reused, `0` disables the cache.
+    #[serde(default = "default_permission_cache_ttl_seconds")]
+    pub permission_cache_ttl_seconds: u64,
     pub smtp_host: String,
     pub smtp_username: String,
     pub smtp_password: String,
@@ -23,3 +123,95 @@ impl AppConfig {
             .try_deserialize()
     }
 }
+
+fn default_app_url() -> String {
+    "http://localhost:8000".to_string()
+}
+
+fn default_jwt_leeway_seconds() -> u64 {
+    60
+}
+
+fn default_argon2_memory_kib() -> u32 {
+    19 * 1024
+}
+
+fn default_argon2_iterations() -> u32 {
+    2
+}
+
+fn default_argon2_parallelism() -> u32 {
+    1
+}
+
+fn default_email_verification_expiration_minutes() -> i64 {
+    24 * 60
+}
+
+fn default_password_reset_expiration_minutes() -> i64 {
+    30
+}
+
+fn default_two_factor_issuer() -> String {
+    "web-app-boilerplate".to_string()
+}
+
+fn default_two_factor_challenge_expiration_minutes() -> i64 {
+    5
+}
+
+fn default_login_max_attempts() -> u32 {
+    5
+}
+
+fn default_login_ip_max_attempts() -> u32 {
+    20
+}
+
+fn default_login_lockout_base_seconds() -> i64 {
+    30
+}
+
+fn default_login_lockout_max_seconds() -> i64 {
+    60 * 60
+}
+
+fn default_login_attempt_window_seconds() -> i64 {
+    15 * 60
+}
+
+fn default_oauth_state_expiration_minutes() -> i64 {
+    10
+}
+
+fn default_auth_cookie_secure() -> bool {
+    true
+}
+
+fn default_auth_cookie_same_site() -> String {
+    "Lax".to_string()
+}
+
+fn default_impersonation_expiration_minutes() -> i64 {
+    15
+}
+
+fn default_password_min_length() -> usize {
+    8
+}
+
+fn default_password_max_length() -> usize {
+    128
+}
+
+fn default_password_history_size() -> u64 {
+    5
+}
+
+fn default_magic_link_expiration_minutes() -> i64 {
+    15
+}
+
+fn default_permission_cache_ttl_seconds() -> u64 {
+    60
+}
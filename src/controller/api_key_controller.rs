@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Router,
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{delete, get},
+};
+use garde::Validate as _;
+
+use crate::{
+    AppState,
+    api_response::JsonResponse,
+    auth::{api_key_store::ApiKeyStore, auth_service::AuthUser, impersonation::Impersonation},
+    error::AppError,
+    extractor::ValidJson,
+    form::api_key_form::CreateApiKeyRequest,
+    models::_entities::user,
+    serializer::{ApiKeySerializer, CreatedApiKeySerializer},
+};
+
+pub async fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_api_keys).post(create_api_key))
+        .route("/{api_key_id}", delete(delete_api_key))
+}
+
+#[axum::debug_handler]
+pub async fn get_api_keys(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let api_keys: Vec<ApiKeySerializer> = ApiKeyStore::list(&app_state.db, user_model.id)
+        .await?
+        .into_iter()
+        .map(|(api_key, permissions)| ApiKeySerializer::new(api_key, permissions))
+        .collect();
+
+    Ok(JsonResponse::data(api_keys, None))
+}
+
+#[axum::debug_handler]
+pub async fn create_api_key(
+    State(app_state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    ValidJson(payload): ValidJson<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    // keys would outlive the impersonation
+    Impersonation::ensure_not_impersonating(&auth_user)?;
+
+    let (api_key, permissions, key) = ApiKeyStore::create(
+        &app_state,
+        auth_user.id,
+        &payload.name,
+        payload.expires_at,
+        &payload.permissions,
+    )
+    .await?;
+
+    Ok(JsonResponse::data(
+        CreatedApiKeySerializer {
+            api_key: ApiKeySerializer::new(api_key, permissions),
+            key,
+        },
+        Some("API key created successfully.".to_string()),
+    ))
+}
+
+#[axum::debug_handler]
+pub async fn delete_api_key(
+    State(app_state): State<Arc<AppState>>,
+    Path(api_key_id): Path<i32>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    ApiKeyStore::revoke(&app_state.db, user_model.id, api_key_id).await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("API key revoked successfully.".to_string()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{self, Request, StatusCode, header},
+    };
+    use dotenvy::dotenv;
+    use sea_orm::{
+        ActiveModelTrait as _, ColumnTrait as _, EntityTrait as _, QueryFilter as _, Set,
+    };
+    use serde_json::{Value, json};
+
+    use crate::{
+        auth::api_key_store::ApiKeyStore,
+        models::_entities::user_permission,
+        routes::create_router,
+        test_utils::{
+            access_token, find_or_create_permission, find_or_create_user, request, send, test_state,
+        },
+    };
+
+    fn with_api_key(uri: &str, api_key: &str) -> Request<Body> {
+        Request::builder()
+            .uri(uri)
+            .header("X-Api-Key", api_key)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_api_key_permissions_are_scoped() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let owner = find_or_create_user(&app_state, "api-key-owner", false).await;
+        let other_user = find_or_create_user(&app_state, "api-key-other-user", false).await;
+        let read_users = find_or_create_permission(&app_state, "read_users").await;
+        find_or_create_permission(&app_state, "create_user").await;
+
+        let has_read_users = user_permission::Entity::find()
+            .filter(user_permission::Column::UserId.eq(owner.id))
+            .filter(user_permission::Column::PermissionId.eq(read_users.id))
+            .one(&app_state.db)
+            .await
+            .unwrap();
+
+        if has_read_users.is_none() {
+            user_permission::ActiveModel {
+                user_id: Set(owner.id),
+                permission_id: Set(read_users.id),
+                ..Default::default()
+            }
+            .insert(&app_state.db)
+            .await
+            .unwrap();
+        }
+
+        let access_token = access_token(&app_state, owner.id).await;
+
+        let (_, _, unscoped_key) = ApiKeyStore::create(
+            &app_state,
+            owner.id,
+            "unscoped",
+            None,
+            &["create_user".to_string()],
+        )
+        .await
+        .unwrap();
+
+        // the other user can't read users, so neither can their keys
+        let (_, _, other_user_key) = ApiKeyStore::create(
+            &app_state,
+            other_user.id,
+            "other",
+            None,
+            &["read_users".to_string()],
+        )
+        .await
+        .unwrap();
+
+        let app = create_router(app_state).await;
+
+        let (status, body) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/api-keys",
+            Some(&access_token),
+            json!({"name": "reporting", "permissions": ["read_users"]}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["permissions"], json!(["read_users"]));
+
+        let api_key = body["data"]["key"].as_str().unwrap().to_string();
+        let api_key_id = body["data"]["id"].as_i64().unwrap();
+
+        let (status, _) = send(&app, with_api_key("/api/users", &api_key)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send(&app, with_api_key("/api/users", &unscoped_key)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = send(
+            &app,
+            Request::builder()
+                .uri("/api/users")
+                .header(header::AUTHORIZATION, format!("ApiKey {other_user_key}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // keys can't manage the account they belong to
+        let (status, _) = send(&app, with_api_key("/api/auth/sessions", &api_key)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = request(
+            &app,
+            http::Method::DELETE,
+            &format!("/api/auth/api-keys/{api_key_id}"),
+            Some(&access_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send(&app, with_api_key("/api/users", &api_key)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -3,40 +3,64 @@ use std::sync::Arc;
 use crate::{
     AppState,
     api_response::JsonResponse,
-    auth::jwt::{UserToken, create_user_token},
+    auth::{
+        auth_cookies::{AuthCookies, REFRESH_TOKEN_COOKIE},
+        deactivation::Deactivation,
+        email_change::EmailChange,
+        email_verification::EmailVerification,
+        identifier::Identifier,
+        jwt::{TokenClaims, TokenType, TwoFactorChallenge},
+        login_throttle::LoginGuard,
+        password::{PasswordHasher, PasswordVerification},
+        password_reset::PasswordReset,
+        token_store::{SessionDevice, TokenStore},
+        two_factor::TwoFactor,
+    },
     error::AppError,
-    extractor::ValidJson,
-    form::user_form::{CreateUserRequest, UserLogin},
-    mails::auth_mails::send_register_mail,
+    extractor::{ClientIp, UserAgent, ValidJson},
+    form::user_form::{
+        CreateUserRequest, ForgotPasswordRequest, RefreshTokenRequest,
+        ResendVerificationEmailRequest, ResetPasswordRequest, UserLogin, VerifyEmailRequest,
+    },
+    mails::auth_mails::{send_password_reset_mail, send_register_mail, send_verification_mail},
     models::_entities::{user, user_profile},
     serializer::UserWithProfileSerializer,
-    utils::verify_password,
 };
 
-use axum::{Router, extract::State, response::IntoResponse, routing::post};
+use axum::{
+    Extension, Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, Method},
+    response::IntoResponse,
+    routing::{get, post},
+};
+use axum_extra::extract::cookie::CookieJar;
 use garde::Validate;
 use sea_orm::{
     ActiveModelTrait as _, ColumnTrait, Condition, EntityTrait, QueryFilter, Set,
     TransactionTrait as _,
 };
-use validator::Validate as _;
-
-pub async fn get_routes() -> Router<Arc<AppState>> {
-    Router::new()
-        .route("/auth/login", post(login))
-        .route("/auth/logout", post(logout))
-}
 
 pub async fn get_login_route() -> Router<Arc<AppState>> {
     Router::new()
         .route("/login", post(login))
         .route("/register", post(register))
+        .route("/refresh", post(refresh))
+        .route("/verify-email", get(verify_email))
+        .route("/verify-email/resend", post(resend_verification_email))
+        .route("/confirm-email-change", get(confirm_email_change))
+        .route("/forgot-password", post(forgot_password))
+        .route("/reset-password", post(reset_password))
 }
 
 pub async fn get_logout_route() -> Router<Arc<AppState>> {
     Router::new().route("/logout", post(logout))
 }
 
+pub async fn get_well_known_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/jwks.json", get(jwks))
+}
+
 #[axum::debug_handler]
 pub async fn register(
     State(app_state): State<Arc<AppState>>,
@@ -47,8 +71,8 @@ pub async fn register(
     let user_exist = user::Entity::find()
         .filter(
             Condition::any()
-                .add(user::Column::Email.eq(&payload.email))
-                .add(user::Column::Username.eq(&payload.username)),
+                .add(Identifier::email_eq(&payload.email))
+                .add(Identifier::username_eq(&payload.username)),
         )
         .one(&app_state.db)
         .await?;
@@ -61,11 +85,20 @@ pub async fn register(
 
     let user_email = payload.email.clone();
 
+    let password_hash = PasswordHasher::new(&app_state.config)?
+        .hash(&payload.password)
+        .await?;
+
     let user_with_profile = app_state
         .db
         .transaction::<_, (user::Model, Option<user_profile::Model>), sea_orm::DbErr>(|txn| {
             Box::pin(async move {
-                let user = user::ActiveModel::from(payload.clone()).insert(txn).await?;
+                let user = user::ActiveModel {
+                    password: Set(password_hash),
+                    ..payload.clone().into()
+                }
+                .insert(txn)
+                .await?;
 
                 let user_profile = user_profile::ActiveModel {
                     id: sea_orm::ActiveValue::NotSet,
@@ -85,12 +118,19 @@ pub async fn register(
             AppError::GenericError(e.to_string())
         })?; // should be database error
 
+    let verification_url = EmailVerification::verification_url(&app_state, &user_with_profile.0)?;
+
     let user_serializer = UserWithProfileSerializer::from(user_with_profile);
 
     println!("{:#?}", user_serializer);
 
-    send_register_mail(app_state, "User Registration Complete", &user_email)
-        .map_err(AppError::GenericError)?;
+    send_register_mail(
+        app_state,
+        "User Registration Complete",
+        &user_email,
+        &verification_url,
+    )
+    .map_err(AppError::GenericError)?;
 
     Ok(JsonResponse::data(user_serializer, None))
 }
@@ -98,87 +138,837 @@ pub async fn register(
 #[axum::debug_handler]
 pub async fn login(
     State(app_state): State<Arc<AppState>>,
+    ClientIp(client_ip): ClientIp,
+    UserAgent(user_agent): UserAgent,
+    jar: CookieJar,
     ValidJson(payload): ValidJson<UserLogin>,
 ) -> Result<impl IntoResponse, AppError> {
     payload.validate()?;
 
+    let login_key = Identifier::login_key(&payload.username);
+
     let user = user::Entity::find()
-        .filter(user::Column::Username.eq(payload.username))
+        .filter(Identifier::login_eq(&payload.username))
         .one(&app_state.db)
+        .await?;
+
+    LoginGuard::check(&app_state, client_ip, &login_key, user.as_ref())?;
+
+    let password_hasher = PasswordHasher::new(&app_state.config)?;
+
+    // unknown users and wrong passwords get the same response after the same amount of work
+    let Some(user) = user else {
+        password_hasher.verify_dummy(&payload.password).await?;
+
+        return Err(LoginGuard::register_failure(&app_state, client_ip, &login_key, None).await);
+    };
+
+    let user = match password_hasher
+        .verify(&user.password, &payload.password)
         .await?
-        .ok_or(AppError::GenericError("User not found.".to_string()))?;
+    {
+        PasswordVerification::Invalid => {
+            return Err(LoginGuard::register_failure(
+                &app_state,
+                client_ip,
+                &login_key,
+                Some(user),
+            )
+            .await);
+        }
+        PasswordVerification::Valid => user,
+        PasswordVerification::ValidNeedsRehash => {
+            let mut user: user::ActiveModel = user.into();
+            user.password = Set(password_hasher.hash(&payload.password).await?);
+            user.update(&app_state.db).await?
+        }
+    };
+
+    Deactivation::ensure_active(&user)?;
+    EmailVerification::ensure_verified(&app_state.config, &user)?;
 
-    if !verify_password(&user.password, &payload.password)? {
-        return Err(AppError::GenericError("Invalid user".to_string()));
+    // failed attempts are only cleared once the second factor is verified as well
+    if user.totp_enabled_at.is_some() {
+        let challenge_token = TwoFactor::challenge_token(&app_state, &user)?;
+
+        return Ok(JsonResponse::data(
+            TwoFactorChallenge { challenge_token },
+            Some("Two-factor authentication required.".to_string()),
+        )
+        .into_response());
     }
 
-    let app_config = app_state.config.to_owned();
+    let user = LoginGuard::register_success(&app_state, user).await?;
 
-    let access_token = create_user_token(
-        &user.email,
-        app_config.access_token_expiration_minutes,
-        &app_config.jwt_secret,
-    )
-    .await
-    .map_err(AppError::GenericError)?;
+    let device = SessionDevice {
+        user_agent,
+        ip_address: client_ip,
+    };
 
-    let refresh_token = create_user_token(
-        &user.email,
-        app_config.refresh_token_expiration_minutes,
-        &app_config.jwt_secret,
-    )
-    .await
-    .map_err(AppError::GenericError)?;
+    let user_token = TokenStore::issue_token_pair(&app_state, &user, device).await?;
+
+    Ok(AuthCookies::token_response(
+        &app_state.config,
+        jar,
+        user_token,
+    ))
+}
 
-    let user_token = UserToken {
-        access_token,
-        refresh_token: Some(refresh_token),
+#[axum::debug_handler]
+pub async fn refresh(
+    State(app_state): State<Arc<AppState>>,
+    method: Method,
+    headers: HeaderMap,
+    jar: CookieJar,
+    ValidJson(payload): ValidJson<RefreshTokenRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let refresh_token = match payload.refresh_token {
+        Some(refresh_token) => refresh_token,
+        None if app_state.config.auth_cookies => {
+            AuthCookies::verify_csrf(&method, &headers, &jar)?;
+
+            jar.get(REFRESH_TOKEN_COOKIE)
+                .map(|cookie| cookie.value().to_string())
+                .ok_or(AppError::EmptyToken)?
+        }
+        None => return Err(AppError::EmptyToken),
     };
 
-    Ok(JsonResponse::data(user_token, None))
+    let token_claims = app_state
+        .jwt_keys
+        .decode(&refresh_token, TokenType::Refresh)?;
+
+    let user = TokenStore::authenticate(&app_state.db, &token_claims).await?;
+
+    EmailVerification::ensure_verified(&app_state.config, &user)?;
+
+    let user_token = TokenStore::rotate(&app_state, &token_claims, &user).await?;
+
+    Ok(AuthCookies::token_response(
+        &app_state.config,
+        jar,
+        user_token,
+    ))
 }
 
-pub async fn logout() {}
+#[axum::debug_handler]
+pub async fn logout(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user_model): Extension<user::Model>,
+    Extension(token_claims): Extension<TokenClaims>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AppError> {
+    TokenStore::revoke(&app_state.db, &token_claims, user_model.id).await?;
+
+    // revoking the family also invalidates the refresh token issued with this access token
+    if let Some(family_id) = token_claims.fam {
+        TokenStore::revoke_family(&app_state.db, family_id).await?;
+    }
+
+    Ok((
+        AuthCookies::clear(&app_state.config, jar),
+        JsonResponse::data(None::<String>, Some("Logged out successfully.".to_string())),
+    ))
+}
+
+#[axum::debug_handler]
+pub async fn verify_email(
+    State(app_state): State<Arc<AppState>>,
+    Query(payload): Query<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    EmailVerification::verify(&app_state, &payload.token).await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Email address verified successfully.".to_string()),
+    ))
+}
+
+/// Confirms an email change from the link sent to the new address.
+#[axum::debug_handler]
+pub async fn confirm_email_change(
+    State(app_state): State<Arc<AppState>>,
+    Query(payload): Query<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    EmailChange::confirm(&app_state, &payload.token).await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Email address changed successfully.".to_string()),
+    ))
+}
+
+/// Sends a new verification link. The link is sent in the background, so neither the response
+/// nor its timing reveals whether the address belongs to an unverified account.
+#[axum::debug_handler]
+pub async fn resend_verification_email(
+    State(app_state): State<Arc<AppState>>,
+    ValidJson(payload): ValidJson<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let user = user::Entity::find()
+        .filter(Identifier::email_eq(&payload.email))
+        .filter(user::Column::EmailVerifiedAt.is_null())
+        .one(&app_state.db)
+        .await?;
+
+    if let Some(user) = user {
+        let verification_url = EmailVerification::verification_url(&app_state, &user)?;
+
+        tokio::spawn(async move {
+            let mail = tokio::task::spawn_blocking(move || {
+                send_verification_mail(
+                    app_state,
+                    "Verify your email address",
+                    &user.email,
+                    &verification_url,
+                )
+            })
+            .await;
+
+            if let Ok(Err(e)) = mail {
+                tracing::error!("Failed to send verification email: {}", e);
+            }
+        });
+    }
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some(
+            "If the address belongs to an unverified account, a verification email has been sent."
+                .to_string(),
+        ),
+    ))
+}
+
+/// Emails a password reset token. The token is created and sent in the background, so
+/// neither the response nor its timing reveals whether the address belongs to an account.
+#[axum::debug_handler]
+pub async fn forgot_password(
+    State(app_state): State<Arc<AppState>>,
+    ValidJson(payload): ValidJson<ForgotPasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let user = user::Entity::find()
+        .filter(Identifier::email_eq(&payload.email))
+        .one(&app_state.db)
+        .await?;
+
+    if let Some(user) = user {
+        tokio::spawn(async move {
+            let reset_token = match PasswordReset::create_token(
+                &app_state.db,
+                &app_state.config,
+                user.id,
+            )
+            .await
+            {
+                Ok(reset_token) => reset_token,
+                Err(e) => {
+                    tracing::error!("Failed to create password reset token: {}", e);
+                    return;
+                }
+            };
+
+            let mail = tokio::task::spawn_blocking(move || {
+                send_password_reset_mail(
+                    app_state,
+                    "Reset your password",
+                    &user.email,
+                    &reset_token,
+                )
+            })
+            .await;
+
+            if let Ok(Err(e)) = mail {
+                tracing::error!("Failed to send password reset email: {}", e);
+            }
+        });
+    }
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some(
+            "If an account with that email exists, a password reset email has been sent."
+                .to_string(),
+        ),
+    ))
+}
+
+#[axum::debug_handler]
+pub async fn reset_password(
+    State(app_state): State<Arc<AppState>>,
+    ValidJson(payload): ValidJson<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    PasswordReset::reset_password(&app_state, &payload.token, &payload.password).await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Password has been reset successfully.".to_string()),
+    ))
+}
+
+/// Publishes the public keys that verify user tokens, in JWK Set format.
+pub async fn jwks(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(app_state.jwt_keys.jwks())
+}
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{collections::HashMap, net::SocketAddr};
 
     use axum::{
         body::{Body, to_bytes},
+        extract::ConnectInfo,
         http::{self, Request, StatusCode, header},
+        response::Response,
     };
+    use axum_extra::extract::cookie::Cookie;
     use dotenvy::dotenv;
+    use sea_orm::{
+        ActiveModelTrait as _, ColumnTrait as _, EntityTrait as _, QueryFilter as _, Set,
+    };
     use serde_json::json;
     use tower::ServiceExt as _;
 
     use crate::{
-        api_response::ErrorResponse, configgg::AppConfig, routes::create_router, state::AppState,
-        utils::connect_to_database,
+        api_response::ErrorResponse,
+        auth::{
+            email_change::EmailChange,
+            email_verification::EmailVerification,
+            jwt::{TokenClaims, TokenType},
+            password::{PasswordHasher, PasswordVerification},
+            password_reset::PasswordReset,
+            token_store::{SessionDevice, TokenStore},
+        },
+        models::_entities::{password_reset_token, user},
+        routes::create_router,
+        test_utils::{
+            access_token, find_or_create_user, post_json, request, test_state, test_state_with,
+            unverified_user, user_with_password,
+        },
     };
 
+    async fn refresh_request(app: &axum::Router, refresh_token: &str) -> Response {
+        post_json(
+            app,
+            "/api/auth/refresh",
+            json!({ "refresh_token": refresh_token }),
+        )
+        .await
+    }
+
     #[tokio::test]
     async fn test_invalid_login() {
         dotenv().ok();
 
-        let app_config = AppConfig::from_env().unwrap();
+        let app_state = test_state().await;
+        user_with_password(&app_state, "invalid-login-user", "correct-horse").await;
 
-        let app = create_router(Arc::new(AppState {
-            db: connect_to_database(&app_config.database_url).await.unwrap(),
-            config: app_config,
-        }))
+        let app = create_router(app_state).await;
+
+        let mut responses = Vec::new();
+
+        // a wrong password and an unknown username can't be told apart
+        for username in ["invalid-login-user", "no-such-user"] {
+            let response = post_json(
+                &app,
+                "/api/auth/login",
+                json!({ "username": username, "password": "password" }),
+            )
+            .await;
+
+            let status = response.status();
+            let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let error_response: ErrorResponse = serde_json::from_slice(&body_bytes).unwrap();
+
+            responses.push((status, error_response));
+        }
+
+        let expected_response = ErrorResponse {
+            error: json!("Invalid username or password."),
+            message: "Authentication Error".to_string(),
+        };
+
+        assert_eq!(responses[0], (StatusCode::UNAUTHORIZED, expected_response));
+        assert_eq!(responses[0], responses[1]);
+    }
+
+    #[tokio::test]
+    async fn test_login_accepts_any_password_the_policy_allowed() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        let password = "long-password-".repeat(8);
+        assert!(password.len() > 100);
+
+        user_with_password(&app_state, "long-password-user", &password).await;
+
+        let response = post_json(
+            &create_router(app_state).await,
+            "/api/auth/login",
+            json!({ "username": "long-password-user", "password": password }),
+        )
         .await;
 
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // the uniqueness validators block on the database
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_identifiers_are_normalized_and_case_insensitive() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        user_with_password(&app_state, "case-login-user", "correct-horse").await;
+
+        let mut user: user::ActiveModel = user::Entity::find()
+            .filter(user::Column::Username.eq("case-login-user"))
+            .one(&app_state.db)
+            .await
+            .unwrap()
+            .unwrap()
+            .into();
+        user.email = Set("  Case-Login-User@Example.COM ".to_string());
+        let user = user.update(&app_state.db).await.unwrap();
+
+        assert_eq!(user.email, "case-login-user@example.com");
+
+        let app = create_router(app_state).await;
+
+        for identifier in ["Case-Login-User", "CASE-LOGIN-USER@example.com"] {
+            let response = post_json(
+                &app,
+                "/api/auth/login",
+                json!({ "username": identifier, "password": "correct-horse" }),
+            )
+            .await;
+
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = post_json(
+            &app,
+            "/api/auth/register",
+            json!({
+                "name": "Case Login User",
+                "username": "CASE-LOGIN-USER",
+                "email": "Case-Login-User@example.com",
+                "password": format!("Register-{}", uuid::Uuid::new_v4()),
+                "address": "",
+                "mobile_number": "",
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+
+        assert!(body["error"]["username"].is_array());
+        assert!(body["error"]["email"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_account_locked_after_repeated_failures() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        user_with_password(&app_state, "locked-out-user", "correct-horse").await;
+
+        let max_attempts = app_state.config.login_max_attempts;
+        let app = create_router(app_state).await;
+
+        let mut statuses = Vec::new();
+
+        for _ in 0..max_attempts {
+            let response = post_json(
+                &app,
+                "/api/auth/login",
+                json!({ "username": "locked-out-user", "password": "wrong-horse" }),
+            )
+            .await;
+
+            statuses.push(response.status());
+        }
+
+        assert_eq!(statuses.last(), Some(&StatusCode::TOO_MANY_REQUESTS));
+        assert!(
+            statuses[..statuses.len() - 1]
+                .iter()
+                .all(|status| *status == StatusCode::UNAUTHORIZED)
+        );
+
+        // the right password doesn't help while the account is locked
+        let response = post_json(
+            &app,
+            "/api/auth/login",
+            json!({ "username": "locked-out-user", "password": "correct-horse" }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+
+        assert!(body["error"]["locked_until"].is_string());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_failures_all_count_towards_lockout() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        user_with_password(&app_state, "concurrent-lockout-user", "correct-horse").await;
+
+        let max_attempts = app_state.config.login_max_attempts;
+        let app = create_router(app_state.clone()).await;
+
+        let mut logins = tokio::task::JoinSet::new();
+
+        for _ in 0..max_attempts {
+            let app = app.clone();
+
+            logins.spawn(async move {
+                post_json(
+                    &app,
+                    "/api/auth/login",
+                    json!({ "username": "concurrent-lockout-user", "password": "wrong-horse" }),
+                )
+                .await
+                .status()
+            });
+        }
+
+        while let Some(status) = logins.join_next().await {
+            assert_ne!(status.unwrap(), StatusCode::INTERNAL_SERVER_ERROR);
+        }
+
+        let user = user::Entity::find()
+            .filter(user::Column::Username.eq("concurrent-lockout-user"))
+            .one(&app_state.db)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(user.failed_login_attempts, max_attempts as i32);
+        assert!(user.locked_until.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_client_ip_locked_after_repeated_failures() {
+        dotenv().ok();
+
+        let app_state = test_state_with(|app_config| app_config.login_ip_max_attempts = 2).await;
+        let app = create_router(app_state).await;
+
+        let login_from = |ip: [u8; 4], username: &str| {
+            let request = Request::builder()
+                .method(http::Method::POST)
+                .uri("/api/auth/login")
+                .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .extension(ConnectInfo(SocketAddr::from((ip, 4000))))
+                .body(Body::from(
+                    json!({ "username": username, "password": "password" }).to_string(),
+                ))
+                .unwrap();
+
+            app.clone().oneshot(request)
+        };
+
+        let response = login_from([10, 0, 0, 1], "ip-throttle-a").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = login_from([10, 0, 0, 1], "ip-throttle-b").await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let response = login_from([10, 0, 0, 1], "ip-throttle-c").await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let response = login_from([10, 0, 0, 2], "ip-throttle-c").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_rejected_on_protected_route() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let refresh_token = app_state
+            .jwt_keys
+            .encode(&TokenClaims::new(
+                "anish@example.com",
+                TokenType::Refresh,
+                app_state.config.refresh_token_expiration_minutes,
+            ))
+            .unwrap();
+
+        let response = create_router(app_state)
+            .await
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::GET)
+                    .uri("/api/users")
+                    .header(header::AUTHORIZATION, format!("Bearer {refresh_token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_access_token_rejected_on_refresh() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let access_token = app_state
+            .jwt_keys
+            .encode(&TokenClaims::new(
+                "anish@example.com",
+                TokenType::Access,
+                app_state.config.access_token_expiration_minutes,
+            ))
+            .unwrap();
+
+        let response = refresh_request(&create_router(app_state).await, &access_token).await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_logout_revokes_access_token() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        let user = unverified_user(&app_state, "logout-user").await;
+
+        let access_token = app_state
+            .jwt_keys
+            .encode(&TokenClaims::for_user(
+                &user,
+                TokenType::Access,
+                app_state.config.access_token_expiration_minutes,
+            ))
+            .unwrap();
+
+        let app = create_router(app_state).await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/api/auth/logout")
+                    .header(header::AUTHORIZATION, format!("Bearer {access_token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
         let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::GET)
+                    .uri("/api/users")
+                    .header(header::AUTHORIZATION, format!("Bearer {access_token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_token_follows_user_id_and_version() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        let user = unverified_user(&app_state, "token-version-user").await;
+
+        let access_token =
+            TokenStore::issue_token_pair(&app_state, &user, SessionDevice::default())
+                .await
+                .unwrap()
+                .access_token;
+
+        let app = create_router(app_state.clone()).await;
+
+        let sessions_request = |access_token: String| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method(http::Method::GET)
+                    .uri("/api/auth/sessions")
+                    .header(header::AUTHORIZATION, format!("Bearer {access_token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        // changing the email doesn't affect tokens
+        let mut active_user: user::ActiveModel = user.clone().into();
+        active_user.email = Set(format!("renamed-{}", user.email));
+        active_user.update(&app_state.db).await.unwrap();
+
+        let response = sessions_request(access_token.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut active_user: user::ActiveModel = user.clone().into();
+        active_user.email = Set(user.email.clone());
+        active_user.update(&app_state.db).await.unwrap();
+
+        TokenStore::bump_token_version(&app_state.db, user.id)
+            .await
+            .unwrap();
+
+        let response = sessions_request(access_token).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_reuse_revokes_family() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        let user = unverified_user(&app_state, "refresh-reuse-user").await;
+
+        let user_token = TokenStore::issue_token_pair(&app_state, &user, SessionDevice::default())
+            .await
+            .unwrap();
+        let first_refresh_token = user_token.refresh_token.unwrap();
+
+        let app = create_router(app_state).await;
+
+        let response = refresh_request(&app, &first_refresh_token).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+        let second_refresh_token = body["data"]["refresh_token"].as_str().unwrap().to_string();
+
+        // replaying the rotated token is rejected and takes the new one down with it
+        let response = refresh_request(&app, &first_refresh_token).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = refresh_request(&app, &second_refresh_token).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_verify_email_link_marks_user_verified() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        let user = unverified_user(&app_state, "verify-link-user").await;
+
+        let verification_url = EmailVerification::verification_url(&app_state, &user).unwrap();
+        let path = verification_url
+            .strip_prefix(&app_state.config.app_url)
+            .unwrap()
+            .to_string();
+
+        let response = create_router(app_state.clone())
+            .await
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::GET)
+                    .uri(path)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let user = user::Entity::find_by_id(user.id)
+            .one(&app_state.db)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(user.email_verified_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_access_token_rejected_as_verification_link() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        let user = unverified_user(&app_state, "verify-access-user").await;
+
+        let access_token = app_state
+            .jwt_keys
+            .encode(&TokenClaims::for_user(
+                &user,
+                TokenType::Access,
+                app_state.config.access_token_expiration_minutes,
+            ))
+            .unwrap();
+
+        let response = create_router(app_state)
+            .await
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::GET)
+                    .uri(format!("/api/auth/verify-email?token={access_token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_unverified_user_rejected_when_verification_required() {
+        dotenv().ok();
+
+        let app_state =
+            test_state_with(|app_config| app_config.require_email_verification = true).await;
+
+        let user = unverified_user(&app_state, "verify-required-user").await;
+
+        let access_token = app_state
+            .jwt_keys
+            .encode(&TokenClaims::for_user(
+                &user,
+                TokenType::Access,
+                app_state.config.access_token_expiration_minutes,
+            ))
+            .unwrap();
+
+        let response = create_router(app_state)
+            .await
             .oneshot(
                 Request::builder()
                     .method(http::Method::POST)
-                    .uri("/api/auth/login")
-                    .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
-                    // .body(Body::empty())
-                    .body(Body::from(
-                        json!({"username":"anish", "password":"password"}).to_string(),
-                    ))
+                    .uri("/api/auth/logout")
+                    .header(header::AUTHORIZATION, format!("Bearer {access_token}"))
+                    .body(Body::empty())
                     .unwrap(),
             )
             .await
@@ -186,16 +976,408 @@ mod tests {
 
         let status = response.status();
         let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
-        let body_str = String::from_utf8(body_bytes.to_vec()).unwrap();
+        let error_response: ErrorResponse = serde_json::from_slice(&body_bytes).unwrap();
+
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(error_response.message, "Email Not Verified");
+    }
 
-        let error_response: ErrorResponse = serde_json::from_str(&body_str).unwrap();
+    #[tokio::test]
+    async fn test_deactivated_user_is_signed_out_until_reactivated() {
+        dotenv().ok();
 
-        let expected_response = ErrorResponse {
-            error: json!("Invalid user"),
-            message: "Error".to_string(),
+        let app_state = test_state().await;
+
+        let admin = find_or_create_user(&app_state, "deactivation-admin", true).await;
+        let user = user_with_password(&app_state, "deactivation-user", "correct-horse").await;
+
+        let admin_token = access_token(&app_state, admin.id).await;
+        let user_token = access_token(&app_state, user.id).await;
+
+        let app = create_router(app_state).await;
+        let login = json!({ "username": "deactivation-user", "password": "correct-horse" });
+
+        let (status, body) = request(
+            &app,
+            http::Method::POST,
+            &format!("/api/users/{}/deactivate", user.id),
+            Some(&admin_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["data"]["deactivated_at"].is_string());
+
+        let (status, _) = request(
+            &app,
+            http::Method::GET,
+            "/api/auth/me",
+            Some(&user_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, body) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/login",
+            None,
+            login.clone(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["message"], "Account Deactivated");
+
+        let (status, _) = request(
+            &app,
+            http::Method::POST,
+            &format!("/api/users/{}/reactivate", user.id),
+            Some(&admin_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = request(&app, http::Method::POST, "/api/auth/login", None, login).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = request(
+            &app,
+            http::Method::POST,
+            &format!("/api/users/{}/deactivate", admin.id),
+            Some(&admin_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_password_reset_token_is_single_use() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        let user = unverified_user(&app_state, "reset-password-user").await;
+
+        let reset_token = PasswordReset::create_token(&app_state.db, &app_state.config, user.id)
+            .await
+            .unwrap();
+
+        let stored_token = password_reset_token::Entity::find()
+            .filter(password_reset_token::Column::UserId.eq(user.id))
+            .one(&app_state.db)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_ne!(stored_token.token_hash, reset_token);
+
+        // reusing a password of an earlier run would be refused
+        let new_password = format!("new-password-{}", uuid::Uuid::new_v4());
+
+        let app = create_router(app_state.clone()).await;
+        let body = json!({ "token": reset_token, "password": new_password });
+
+        let response = post_json(&app, "/api/auth/reset-password", body.clone()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = post_json(&app, "/api/auth/reset-password", body).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let user = user::Entity::find_by_id(user.id)
+            .one(&app_state.db)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            PasswordHasher::new(&app_state.config)
+                .unwrap()
+                .verify(&user.password, &new_password)
+                .await
+                .unwrap(),
+            PasswordVerification::Valid
+        );
+    }
+
+    #[tokio::test]
+    async fn test_password_reset_applies_password_policy() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        let user = unverified_user(&app_state, "reset-policy-user").await;
+
+        let app = create_router(app_state.clone()).await;
+
+        let reset = |password: String| {
+            let app = app.clone();
+            let app_state = app_state.clone();
+
+            async move {
+                let reset_token =
+                    PasswordReset::create_token(&app_state.db, &app_state.config, user.id)
+                        .await
+                        .unwrap();
+
+                let response = post_json(
+                    &app,
+                    "/api/auth/reset-password",
+                    json!({ "token": reset_token, "password": password }),
+                )
+                .await;
+
+                let status = response.status();
+                let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+                (
+                    status,
+                    serde_json::from_slice::<serde_json::Value>(&body_bytes).unwrap(),
+                )
+            }
         };
 
+        let first_password = format!("first-password-{}", uuid::Uuid::new_v4());
+        let second_password = format!("second-password-{}", uuid::Uuid::new_v4());
+
+        let (status, body) = reset("short".to_string()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]["password"].is_array());
+
+        let (status, _) = reset(format!("reset-policy-user-{}", uuid::Uuid::new_v4())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = reset(first_password.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = reset(second_password).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // the replaced password is remembered too
+        let (status, body) = reset(first_password).await;
         assert_eq!(status, StatusCode::BAD_REQUEST);
-        assert_eq!(error_response, expected_response);
+        assert!(body["error"]["password"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_email_change_takes_effect_once_confirmed() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let mut user: user::ActiveModel = unverified_user(&app_state, "email-change-user")
+            .await
+            .into();
+        user.email = Set("email-change-user@example.com".to_string());
+        user.pending_email = Set(None);
+        let user = user.update(&app_state.db).await.unwrap();
+
+        let other_user = unverified_user(&app_state, "email-change-other-user").await;
+        let user_id = user.id;
+
+        let confirmation_token = |new_email: &str| {
+            app_state
+                .jwt_keys
+                .encode(
+                    &TokenClaims::new(
+                        &user_id.to_string(),
+                        TokenType::EmailChange,
+                        app_state.config.email_verification_expiration_minutes,
+                    )
+                    .with_email(new_email),
+                )
+                .unwrap()
+        };
+
+        let app = create_router(app_state.clone()).await;
+
+        let confirm = |token: String| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method(http::Method::GET)
+                    .uri(format!("/api/auth/confirm-email-change?token={token}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        // an address taken before the confirmation is refused cleanly
+        let taken_email = format!("{}@example.com", uuid::Uuid::new_v4());
+
+        let user = EmailChange::request(&app_state, user, &taken_email)
+            .await
+            .unwrap();
+        assert_eq!(user.email, "email-change-user@example.com");
+        assert_eq!(user.pending_email.as_deref(), Some(taken_email.as_str()));
+
+        let mut other_user: user::ActiveModel = other_user.into();
+        other_user.email = Set(taken_email.clone());
+        other_user.update(&app_state.db).await.unwrap();
+
+        let response = confirm(confirmation_token(&taken_email)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let user = user::Entity::find_by_id(user.id)
+            .one(&app_state.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.email, "email-change-user@example.com");
+        assert!(user.pending_email.is_none());
+
+        let new_email = format!("{}@example.com", uuid::Uuid::new_v4());
+
+        EmailChange::request(&app_state, user.clone(), &new_email)
+            .await
+            .unwrap();
+
+        let response = confirm(confirmation_token(&new_email)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let user = user::Entity::find_by_id(user.id)
+            .one(&app_state.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.email, new_email);
+        assert!(user.email_verified_at.is_some());
+
+        // the link is spent once the change went through
+        let response = confirm(confirmation_token(&new_email)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_forgot_password_response_does_not_reveal_account() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        let user = unverified_user(&app_state, "forgot-password-user").await;
+
+        let app = create_router(app_state).await;
+
+        let mut responses = Vec::new();
+
+        for email in [user.email.as_str(), "nobody@example.com"] {
+            let response =
+                post_json(&app, "/api/auth/forgot-password", json!({ "email": email })).await;
+
+            let status = response.status();
+            let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+            responses.push((status, body_bytes));
+        }
+
+        assert_eq!(responses[0].0, StatusCode::OK);
+        assert_eq!(responses[0], responses[1]);
+    }
+
+    /// Cookies set by the response, by name.
+    fn set_cookies(response: &Response) -> HashMap<String, Cookie<'static>> {
+        response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|header| Cookie::parse(header.to_str().unwrap().to_string()).unwrap())
+            .map(|cookie| (cookie.name().to_string(), cookie))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_cookie_session_requires_csrf_token() {
+        dotenv().ok();
+
+        let app_state = test_state_with(|app_config| app_config.auth_cookies = true).await;
+        user_with_password(&app_state, "cookie-session-user", "correct-horse").await;
+
+        let app = create_router(app_state).await;
+
+        let response = post_json(
+            &app,
+            "/api/auth/login",
+            json!({ "username": "cookie-session-user", "password": "correct-horse" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let cookies = set_cookies(&response);
+        assert_eq!(cookies["access_token"].http_only(), Some(true));
+        assert_eq!(cookies["refresh_token"].path(), Some("/api/auth"));
+        assert_ne!(cookies["csrf_token"].http_only(), Some(true));
+
+        let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+        assert!(body["data"].get("access_token").is_none());
+        assert_eq!(body["data"]["csrf_token"], cookies["csrf_token"].value());
+
+        let cookie_header = |with_refresh_token: bool| {
+            let mut names = vec!["access_token", "csrf_token"];
+            if with_refresh_token {
+                names.push("refresh_token");
+            }
+
+            names
+                .into_iter()
+                .map(|name| format!("{name}={}", cookies[name].value()))
+                .collect::<Vec<_>>()
+                .join("; ")
+        };
+
+        let request = |method: http::Method, uri: &str, csrf_token: Option<&str>| {
+            let mut request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(header::COOKIE, cookie_header(uri.ends_with("/refresh")))
+                .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+
+            if let Some(csrf_token) = csrf_token {
+                request = request.header("X-CSRF-Token", csrf_token);
+            }
+
+            request.body(Body::from("{}")).unwrap()
+        };
+
+        let csrf_token = cookies["csrf_token"].value();
+
+        // safe methods don't need the CSRF token
+        let response = app
+            .clone()
+            .oneshot(request(http::Method::GET, "/api/auth/sessions", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for csrf_token in [None, Some("forged")] {
+            let response = app
+                .clone()
+                .oneshot(request(http::Method::POST, "/api/auth/logout", csrf_token))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+
+        let response = app
+            .clone()
+            .oneshot(request(
+                http::Method::POST,
+                "/api/auth/refresh",
+                Some(csrf_token),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(set_cookies(&response).contains_key("access_token"));
+
+        let response = app
+            .clone()
+            .oneshot(request(
+                http::Method::POST,
+                "/api/auth/logout",
+                Some(csrf_token),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(set_cookies(&response)["access_token"].value(), "");
     }
 }
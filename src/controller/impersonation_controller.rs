@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Router,
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::post,
+};
+
+use crate::{
+    AppState,
+    api_response::JsonResponse,
+    auth::{auth_service::AuthUser, impersonation::Impersonation},
+    error::AppError,
+};
+
+pub async fn get_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/{user_id}", post(impersonate))
+}
+
+/// Issues a short-lived access token to act as the user, for superadmins and users with the
+/// `impersonate_users` permission.
+#[axum::debug_handler]
+pub async fn impersonate(
+    State(app_state): State<Arc<AppState>>,
+    Path(user_id): Path<i32>,
+    Extension(auth_user): Extension<AuthUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let user_token = Impersonation::start(&app_state, &auth_user, user_id).await?;
+
+    Ok(JsonResponse::data(
+        user_token,
+        Some("Impersonation started.".to_string()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{self, StatusCode};
+    use dotenvy::dotenv;
+    use serde_json::{Value, json};
+
+    use crate::{
+        auth::jwt::TokenType,
+        routes::create_router,
+        test_utils::{access_token, find_or_create_user, request, test_state},
+    };
+
+    #[tokio::test]
+    async fn test_impersonation() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let admin = find_or_create_user(&app_state, "impersonation-admin", true).await;
+        let target = find_or_create_user(&app_state, "impersonation-target", false).await;
+
+        let admin_token = access_token(&app_state, admin.id).await;
+        let target_token = access_token(&app_state, target.id).await;
+
+        let app = create_router(app_state.clone()).await;
+
+        // regular users can't impersonate
+        let (status, _) = request(
+            &app,
+            http::Method::POST,
+            &format!("/api/auth/impersonate/{}", admin.id),
+            Some(&target_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = request(
+            &app,
+            http::Method::POST,
+            &format!("/api/auth/impersonate/{}", target.id),
+            Some(&admin_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["data"]["refresh_token"].is_null());
+
+        let impersonation_token = body["data"]["access_token"].as_str().unwrap();
+        let token_claims = app_state
+            .jwt_keys
+            .decode(impersonation_token, TokenType::Access)
+            .unwrap();
+        assert_eq!(token_claims.sub, target.id.to_string());
+        assert_eq!(token_claims.act.unwrap().sub, admin.id.to_string());
+
+        let (status, _) = request(
+            &app,
+            http::Method::GET,
+            "/api/auth/sessions",
+            Some(impersonation_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // impersonated sessions can't impersonate further or create credentials
+        let (status, _) = request(
+            &app,
+            http::Method::POST,
+            &format!("/api/auth/impersonate/{}", admin.id),
+            Some(impersonation_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/api-keys",
+            Some(impersonation_token),
+            json!({"name": "impersonated", "permissions": []}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // nor take over the account through its second factor
+        for uri in [
+            "/api/auth/2fa/enroll",
+            "/api/auth/2fa/confirm",
+            "/api/auth/2fa/disable",
+        ] {
+            let (status, _) = request(
+                &app,
+                http::Method::POST,
+                uri,
+                Some(impersonation_token),
+                json!({"code": "123456"}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_impersonated_admin_cant_change_emails() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let admin = find_or_create_user(&app_state, "impersonation-admin", true).await;
+        let other_admin = find_or_create_user(&app_state, "impersonation-other-admin", true).await;
+        let target = find_or_create_user(&app_state, "impersonation-email-target", false).await;
+
+        let admin_token = access_token(&app_state, admin.id).await;
+
+        let app = create_router(app_state.clone()).await;
+
+        let (status, body) = request(
+            &app,
+            http::Method::POST,
+            &format!("/api/auth/impersonate/{}", other_admin.id),
+            Some(&admin_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let impersonation_token = body["data"]["access_token"].as_str().unwrap();
+        let uri = format!("/api/users/{}", target.id);
+
+        let (status, _) = request(
+            &app,
+            http::Method::PUT,
+            &uri,
+            Some(impersonation_token),
+            json!({
+                "name": target.name,
+                "username": target.username,
+                "email": "impersonation-changed@example.com",
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // other edits are still allowed
+        let (status, _) = request(
+            &app,
+            http::Method::PUT,
+            &uri,
+            Some(impersonation_token),
+            json!({
+                "name": target.name,
+                "username": target.username,
+                "email": target.email,
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
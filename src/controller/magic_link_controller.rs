@@ -0,0 +1,262 @@
+use std::sync::Arc;
+
+use axum::{
+    Form, Router,
+    extract::{Query, State},
+    response::{Html, IntoResponse},
+    routing::{get, post},
+};
+use axum_extra::extract::cookie::CookieJar;
+use garde::Validate as _;
+use sailfish::TemplateSimple;
+use sea_orm::{EntityTrait as _, QueryFilter as _};
+
+use crate::{
+    AppState,
+    api_response::JsonResponse,
+    auth::{
+        auth_cookies::AuthCookies,
+        identifier::Identifier,
+        jwt::TwoFactorChallenge,
+        magic_link::MagicLink,
+        token_store::{SessionDevice, TokenStore},
+        two_factor::TwoFactor,
+    },
+    error::AppError,
+    extractor::{ClientIp, UserAgent, ValidJson},
+    form::magic_link_form::{MagicLinkRequest, MagicLinkVerifyRequest},
+    mails::auth_mails::send_magic_link_mail,
+    models::_entities::user,
+};
+
+pub async fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", post(request_magic_link))
+        .route("/verify", get(confirm_magic_link).post(verify_magic_link))
+}
+
+#[derive(TemplateSimple)]
+#[template(path = "magic_link_page.stpl")]
+struct MagicLinkPageTemplate {
+    token: String,
+}
+
+/// Emails a sign in link. Like `forgot_password`, the link is sent in the background so the
+/// response doesn't reveal whether the address belongs to an account.
+#[axum::debug_handler]
+pub async fn request_magic_link(
+    State(app_state): State<Arc<AppState>>,
+    ValidJson(payload): ValidJson<MagicLinkRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if !app_state.config.magic_link_login {
+        return Err(AppError::Forbidden);
+    }
+
+    payload.validate()?;
+
+    let user = user::Entity::find()
+        .filter(Identifier::email_eq(&payload.email))
+        .one(&app_state.db)
+        .await?;
+
+    if let Some(user) = user {
+        let login_url = MagicLink::login_url(&app_state, &user)?;
+
+        tokio::spawn(async move {
+            let mail = tokio::task::spawn_blocking(move || {
+                send_magic_link_mail(app_state, "Your sign in link", &user.email, &login_url)
+            })
+            .await;
+
+            if let Ok(Err(e)) = mail {
+                tracing::error!("Failed to send magic link email: {}", e);
+            }
+        });
+    }
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("If an account with that email exists, a sign in link has been sent.".to_string()),
+    ))
+}
+
+/// Page the emailed link opens, which signs in only once submitted. Following the link alone,
+/// as mail scanners and prefetchers do, doesn't use it up.
+#[axum::debug_handler]
+pub async fn confirm_magic_link(
+    State(app_state): State<Arc<AppState>>,
+    Query(payload): Query<MagicLinkVerifyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if !app_state.config.magic_link_login {
+        return Err(AppError::Forbidden);
+    }
+
+    payload.validate()?;
+
+    let page = MagicLinkPageTemplate {
+        token: payload.token,
+    }
+    .render_once()
+    .map_err(|e| AppError::GenericError(e.to_string()))?;
+
+    Ok(Html(page))
+}
+
+/// Signs in with the token of an emailed link, responding like `login`.
+#[axum::debug_handler]
+pub async fn verify_magic_link(
+    State(app_state): State<Arc<AppState>>,
+    ClientIp(client_ip): ClientIp,
+    UserAgent(user_agent): UserAgent,
+    jar: CookieJar,
+    Form(payload): Form<MagicLinkVerifyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if !app_state.config.magic_link_login {
+        return Err(AppError::Forbidden);
+    }
+
+    payload.validate()?;
+
+    let user = MagicLink::consume(&app_state, &payload.token).await?;
+
+    // the link stands in for the password, not for the second factor
+    if user.totp_enabled_at.is_some() {
+        let challenge_token = TwoFactor::challenge_token(&app_state, &user)?;
+
+        return Ok(JsonResponse::data(
+            TwoFactorChallenge { challenge_token },
+            Some("Two-factor authentication required.".to_string()),
+        )
+        .into_response());
+    }
+
+    let device = SessionDevice {
+        user_agent,
+        ip_address: client_ip,
+    };
+
+    let user_token = TokenStore::issue_token_pair(&app_state, &user, device).await?;
+
+    Ok(AuthCookies::token_response(
+        &app_state.config,
+        jar,
+        user_token,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{Body, to_bytes},
+        http::{self, Request, StatusCode, header},
+    };
+    use dotenvy::dotenv;
+    use serde_json::json;
+    use tower::ServiceExt as _;
+
+    use crate::{
+        auth::magic_link::MagicLink,
+        error::AppError,
+        routes::create_router,
+        test_utils::{find_or_create_user, send, test_state_with},
+    };
+
+    fn request_link(email: &str) -> Request<Body> {
+        Request::builder()
+            .method(http::Method::POST)
+            .uri("/api/auth/magic-link")
+            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(json!({ "email": email }).to_string()))
+            .unwrap()
+    }
+
+    fn open_link(login_url: &str) -> Request<Body> {
+        let (_, path) = login_url.split_once("/api/").unwrap();
+
+        Request::builder()
+            .method(http::Method::GET)
+            .uri(format!("/api/{path}"))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    /// Submits the page the link opens.
+    fn verify_link(login_url: &str) -> Request<Body> {
+        let (_, query) = login_url.split_once('?').unwrap();
+
+        Request::builder()
+            .method(http::Method::POST)
+            .uri("/api/auth/magic-link/verify")
+            .header(
+                header::CONTENT_TYPE,
+                mime::APPLICATION_WWW_FORM_URLENCODED.as_ref(),
+            )
+            .body(Body::from(query.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_magic_link_signs_in_once() {
+        dotenv().ok();
+
+        let app_state = test_state_with(|app_config| app_config.magic_link_login = true).await;
+
+        let user = find_or_create_user(&app_state, "magic-link-user", false).await;
+
+        let login_url = MagicLink::login_url(&app_state, &user).unwrap();
+
+        let app = create_router(app_state).await;
+
+        let (status, _) = send(&app, request_link(&user.email)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // opening the link, as a mail scanner would, doesn't sign in
+        let response = app.clone().oneshot(open_link(&login_url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body_bytes).contains("method=\"post\""));
+
+        let (status, body) = send(&app, verify_link(&login_url)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["data"]["access_token"].is_string());
+        assert!(body["data"]["refresh_token"].is_string());
+
+        let (status, _) = send(&app, verify_link(&login_url)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_magic_link_login_can_be_disabled() {
+        dotenv().ok();
+
+        let app_state = test_state_with(|app_config| app_config.magic_link_login = false).await;
+        let app = create_router(app_state).await;
+
+        let (status, _) = send(&app, request_link("magic-link-user@example.com")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_redemptions_sign_in_once() {
+        dotenv().ok();
+
+        let app_state = test_state_with(|app_config| app_config.magic_link_login = true).await;
+
+        let user = find_or_create_user(&app_state, "magic-link-raced-user", false).await;
+
+        let login_url = MagicLink::login_url(&app_state, &user).unwrap();
+        let (_, token) = login_url.split_once("token=").unwrap();
+
+        let (first, second) = tokio::join!(
+            MagicLink::consume(&app_state, token),
+            MagicLink::consume(&app_state, token),
+        );
+
+        let (signed_in, rejected): (Vec<_>, Vec<_>) =
+            [first, second].into_iter().partition(Result::is_ok);
+
+        assert_eq!(signed_in.len(), 1);
+        assert!(matches!(rejected[..], [Err(AppError::InvalidToken)]));
+    }
+}
@@ -0,0 +1,416 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Router,
+    extract::State,
+    response::IntoResponse,
+    routing::{get, post},
+};
+use axum_extra::extract::cookie::CookieJar;
+use garde::Validate as _;
+use sea_orm::{
+    ActiveModelTrait as _, ActiveValue::NotSet, ColumnTrait as _, EntityTrait as _,
+    ModelTrait as _, QueryFilter as _, Set, TransactionTrait as _, TryIntoModel as _,
+};
+
+use crate::{
+    AppState,
+    api_response::JsonResponse,
+    auth::{
+        auth_cookies::AuthCookies,
+        auth_service::{AuthService, AuthUser},
+        identifier::Identifier,
+        impersonation::Impersonation,
+        login_throttle::LoginGuard,
+        password::{PasswordHasher, PasswordVerification},
+        password_policy::PasswordHistory,
+        token_store::{SessionDevice, TokenStore},
+    },
+    error::AppError,
+    extractor::{ClientIp, UserAgent, ValidJson},
+    form::user_form::{ChangePasswordRequest, UpdateMeRequest},
+    models::_entities::{user, user_profile},
+    serializer::{EffectivePermissionsSerializer, UserWithProfileSerializer},
+};
+
+pub async fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_me).patch(update_me))
+        .route("/password", post(change_password))
+        .route("/permissions", get(get_my_permissions))
+}
+
+#[axum::debug_handler]
+pub async fn get_me(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let profile = user_model
+        .find_related(user_profile::Entity)
+        .one(&app_state.db)
+        .await?;
+
+    let user_serializer = UserWithProfileSerializer::from((user_model, profile));
+
+    Ok(JsonResponse::data(user_serializer, None))
+}
+
+#[axum::debug_handler]
+pub async fn update_me(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user_model): Extension<user::Model>,
+    ValidJson(payload): ValidJson<UpdateMeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    if let Some(username) = &payload.username {
+        // another account's username, in any case, is taken
+        let username_taken = user::Entity::find()
+            .filter(Identifier::username_eq(username))
+            .filter(user::Column::Id.ne(user_model.id))
+            .one(&app_state.db)
+            .await?
+            .is_some();
+
+        if username_taken {
+            return Err(AppError::GenericError(
+                "A user with this username already exists.".to_string(),
+            ));
+        }
+    }
+
+    let profile = user_model
+        .find_related(user_profile::Entity)
+        .one(&app_state.db)
+        .await?;
+
+    let mut user: user::ActiveModel = user_model.into();
+
+    if let Some(name) = payload.name {
+        user.name = Set(name);
+    }
+
+    if let Some(username) = payload.username {
+        user.username = Set(username);
+    }
+
+    let user = user.update(&app_state.db).await?;
+
+    let profile = match (payload.address, payload.mobile_number) {
+        (None, None) => profile,
+        (address, mobile_number) => {
+            let mut profile: user_profile::ActiveModel = match profile {
+                Some(profile) => profile.into(),
+                None => user_profile::ActiveModel {
+                    id: NotSet,
+                    user_id: Set(user.id),
+                    address: Set(None),
+                    mobile_number: Set(None),
+                },
+            };
+
+            if let Some(address) = address {
+                profile.address = Set(Some(address));
+            }
+
+            if let Some(mobile_number) = mobile_number {
+                profile.mobile_number = Set(Some(mobile_number));
+            }
+
+            Some(profile.save(&app_state.db).await?.try_into_model()?)
+        }
+    };
+
+    let user_serializer = UserWithProfileSerializer::from((user, profile));
+
+    Ok(JsonResponse::data(
+        user_serializer,
+        Some("Profile updated successfully.".to_string()),
+    ))
+}
+
+/// Changes the password after checking the current one. Every other session is signed out, and
+/// the current one continues with the newly issued tokens.
+#[axum::debug_handler]
+pub async fn change_password(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user_model): Extension<user::Model>,
+    Extension(auth_user): Extension<AuthUser>,
+    ClientIp(client_ip): ClientIp,
+    UserAgent(user_agent): UserAgent,
+    jar: CookieJar,
+    ValidJson(payload): ValidJson<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    Impersonation::ensure_not_impersonating(&auth_user)?;
+
+    // wrong current passwords count towards the same lockout as failed logins
+    let login_key = Identifier::login_key(&user_model.username);
+
+    LoginGuard::check(&app_state, client_ip, &login_key, Some(&user_model))?;
+
+    let password_hasher = PasswordHasher::new(&app_state.config)?;
+
+    if password_hasher
+        .verify(&user_model.password, &payload.current_password)
+        .await?
+        == PasswordVerification::Invalid
+    {
+        return match LoginGuard::register_failure(
+            &app_state,
+            client_ip,
+            &login_key,
+            Some(user_model),
+        )
+        .await
+        {
+            AppError::InvalidCredentials => {
+                let mut report = garde::Report::new();
+                report.append(
+                    garde::Path::new("current_password"),
+                    garde::Error::new("Current password is incorrect."),
+                );
+
+                Err(report.into())
+            }
+            e => Err(e),
+        };
+    }
+
+    let user_model = LoginGuard::register_success(&app_state, user_model).await?;
+
+    app_state.password_policy.validate(
+        &payload.password,
+        &[&user_model.username, &user_model.email],
+    )?;
+    PasswordHistory::ensure_not_reused(
+        &app_state.db,
+        &app_state.config,
+        &user_model,
+        &payload.password,
+    )
+    .await?;
+
+    let password = password_hasher.hash(&payload.password).await?;
+
+    let txn = app_state.db.begin().await?;
+
+    PasswordHistory::record(&txn, &app_state.config, &user_model).await?;
+
+    let mut user: user::ActiveModel = user_model.into();
+    user.password = Set(password);
+    let user = user.update(&txn).await?;
+
+    TokenStore::revoke_all_for_user(&txn, user.id).await?;
+
+    txn.commit().await?;
+
+    // the revocation bumped the token version the new tokens must carry
+    let user = user::Entity::find_by_id(user.id)
+        .one(&app_state.db)
+        .await?
+        .ok_or(AppError::InvalidToken)?;
+
+    let device = SessionDevice {
+        user_agent,
+        ip_address: client_ip,
+    };
+
+    let user_token = TokenStore::issue_token_pair(&app_state, &user, device).await?;
+
+    Ok(AuthCookies::token_response(
+        &app_state.config,
+        jar,
+        user_token,
+    ))
+}
+
+#[axum::debug_handler]
+pub async fn get_my_permissions(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    let permissions = AuthService::effective_permissions(&app_state, &user_model)
+        .await?
+        .into_iter()
+        .map(|permission| permission.code_name)
+        .collect();
+
+    // the roles the user was given and the ones those inherit from
+    let roles = AuthService::user_grants(&app_state, &user_model)
+        .await?
+        .roles
+        .clone();
+
+    Ok(JsonResponse::data(
+        EffectivePermissionsSerializer {
+            is_superadmin: user_model.is_superadmin,
+            permissions,
+            roles,
+        },
+        None,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{self, StatusCode};
+    use dotenvy::dotenv;
+    use serde_json::json;
+
+    use crate::{
+        routes::create_router,
+        test_utils::{access_token, request, test_state, user_with_password},
+    };
+
+    #[tokio::test]
+    async fn test_me_endpoints() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        // passwords of earlier runs are in the password history
+        let password = format!("current-password-{}", uuid::Uuid::new_v4());
+        let new_password = format!("changed-password-{}", uuid::Uuid::new_v4());
+
+        let user = user_with_password(&app_state, "me-user", &password).await;
+        let access_token = access_token(&app_state, user.id).await;
+
+        let app = create_router(app_state).await;
+
+        let (status, body) = request(
+            &app,
+            http::Method::GET,
+            "/api/auth/me",
+            Some(&access_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["username"], "me-user");
+
+        let (status, body) = request(
+            &app,
+            http::Method::PATCH,
+            "/api/auth/me",
+            Some(&access_token),
+            json!({ "address": "221B Baker Street" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["profile"]["address"], "221B Baker Street");
+
+        // a username with '@' would be looked up as an email address at sign in
+        let (status, body) = request(
+            &app,
+            http::Method::PATCH,
+            "/api/auth/me",
+            Some(&access_token),
+            json!({ "username": "someone@example.com" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]["username"].is_array());
+
+        let (status, body) = request(
+            &app,
+            http::Method::GET,
+            "/api/auth/me/permissions",
+            Some(&access_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["is_superadmin"], false);
+        assert!(body["data"]["permissions"].is_array());
+
+        let (status, body) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/me/password",
+            Some(&access_token),
+            json!({ "current_password": "wrong-password", "password": new_password }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"]["current_password"].is_array());
+
+        let (status, body) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/me/password",
+            Some(&access_token),
+            json!({ "current_password": password, "password": new_password }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let new_access_token = body["data"]["access_token"].as_str().unwrap();
+
+        // the password change signed out every earlier session
+        let (status, _) = request(
+            &app,
+            http::Method::GET,
+            "/api/auth/me",
+            Some(&access_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = request(
+            &app,
+            http::Method::GET,
+            "/api/auth/me",
+            Some(new_access_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_current_passwords_lock_the_account() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let password = format!("current-password-{}", uuid::Uuid::new_v4());
+        let new_password = format!("changed-password-{}", uuid::Uuid::new_v4());
+
+        let user = user_with_password(&app_state, "me-locked-out-user", &password).await;
+        let access_token = access_token(&app_state, user.id).await;
+
+        let max_attempts = app_state.config.login_max_attempts;
+        let app = create_router(app_state).await;
+
+        let mut statuses = Vec::new();
+
+        for _ in 0..max_attempts {
+            let (status, _) = request(
+                &app,
+                http::Method::POST,
+                "/api/auth/me/password",
+                Some(&access_token),
+                json!({ "current_password": "wrong-password", "password": new_password }),
+            )
+            .await;
+
+            statuses.push(status);
+        }
+
+        assert_eq!(statuses.last(), Some(&StatusCode::TOO_MANY_REQUESTS));
+
+        // the right password doesn't help while the account is locked
+        let (status, _) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/me/password",
+            Some(&access_token),
+            json!({ "current_password": password, "password": new_password }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+}
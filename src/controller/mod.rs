@@ -1,5 +1,12 @@
+pub mod api_key_controller;
 pub mod auth_controller;
+pub mod impersonation_controller;
+pub mod magic_link_controller;
+pub mod me_controller;
+pub mod oauth_controller;
 pub mod permission_controller;
 pub mod role_controller;
+pub mod session_controller;
+pub mod two_factor_controller;
 pub mod user_controller;
 pub mod user_role_controller;
@@ -1,10 +1,8 @@
 use std::sync::Arc;
 
 use axum::{
-    Extension, Router,
     extract::{Path, State},
     response::IntoResponse,
-    routing::get,
 };
 use garde::Validate as _;
 use sea_orm::{ActiveModelTrait, DbErr, EntityTrait, IntoActiveModel, Set};
@@ -12,32 +10,33 @@ use sea_orm::{ActiveModelTrait, DbErr, EntityTrait, IntoActiveModel, Set};
 use crate::{
     AppState,
     api_response::JsonResponse,
-    auth::auth_service::AuthService,
+    auth::guarded_router::{GuardedRouter, get},
     error::AppError,
     extractor::ValidJson,
     form::permission_form::CreatePermissionRequest,
-    models::_entities::{permission, user},
+    models::_entities::permission,
     serializer::PermissionSerializer,
 };
 
-pub async fn get_routes() -> Router<Arc<AppState>> {
-    Router::new()
-        .route("/", get(get_permissions).post(create_permission))
+pub async fn get_routes() -> GuardedRouter {
+    GuardedRouter::new()
+        .route(
+            "/",
+            get("read_permissions", get_permissions).post("create_permission", create_permission),
+        )
+        .route("/cache", get("read_permission_cache", get_permission_cache))
         .route(
             "/{permission_id}",
-            get(get_permission)
-                .put(update_permission)
-                .delete(delete_permission),
+            get("read_permission", get_permission)
+                .put("update_permission", update_permission)
+                .delete("delete_permission", delete_permission),
         )
 }
 
 #[axum::debug_handler]
 pub async fn get_permissions(
     State(app_state): State<Arc<AppState>>,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "read_permissions").await?;
-
     let permissions: Vec<PermissionSerializer> = permission::Entity::find()
         .all(&app_state.db)
         .await?
@@ -51,11 +50,8 @@ pub async fn get_permissions(
 #[axum::debug_handler]
 pub async fn create_permission(
     State(app_state): State<Arc<AppState>>,
-    Extension(user_model): Extension<user::Model>,
     ValidJson(payload): ValidJson<CreatePermissionRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "create_permission").await?;
-
     payload.validate()?;
 
     let permission: PermissionSerializer = payload
@@ -70,10 +66,7 @@ pub async fn create_permission(
 pub async fn get_permission(
     State(app_state): State<Arc<AppState>>,
     Path(permission_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "read_permission").await?;
-
     let permission_serializer: PermissionSerializer = permission::Entity::find_by_id(permission_id)
         .one(&app_state.db)
         .await?
@@ -85,11 +78,8 @@ pub async fn get_permission(
 pub async fn update_permission(
     State(app_state): State<Arc<AppState>>,
     Path(permission_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
     ValidJson(payload): ValidJson<CreatePermissionRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "update_permission").await?;
-
     let permission = permission::Entity::find_by_id(permission_id)
         .one(&app_state.db)
         .await?
@@ -105,23 +95,107 @@ pub async fn update_permission(
     let permission_serializer: PermissionSerializer =
         permission.update(&app_state.db).await?.into();
 
+    app_state.permission_cache.invalidate_all();
+
     Ok(JsonResponse::data(permission_serializer, None))
 }
 pub async fn delete_permission(
     State(app_state): State<Arc<AppState>>,
     Path(permission_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "delete_permission").await?;
-
     let res = permission::Entity::delete_by_id(permission_id)
         .exec(&app_state.db)
         .await?;
 
-    println!("{:?}", res);
+    if res.rows_affected > 0 {
+        app_state.permission_cache.invalidate_all();
+    }
 
     Ok(JsonResponse::data(
         None::<String>,
         Some("Permission deleted successfully".to_string()),
     ))
 }
+
+/// Hit and miss counts of the cache `AuthService::user_grants` reads through.
+#[axum::debug_handler]
+pub async fn get_permission_cache(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(JsonResponse::data(app_state.permission_cache.stats(), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{self, StatusCode};
+    use dotenvy::dotenv;
+    use sea_orm::{
+        ActiveModelTrait as _, ActiveValue::NotSet, ColumnTrait as _, EntityTrait as _,
+        QueryFilter as _, Set,
+    };
+    use serde_json::Value;
+
+    use crate::{
+        models::_entities::user_permission,
+        routes::create_router,
+        test_utils::{
+            access_token, find_or_create_permission, find_or_create_user, request, test_state,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_permission_cache_stats() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let user = find_or_create_user(&app_state, "permission-cache-reader", false).await;
+        let permission = find_or_create_permission(&app_state, "read_permission_cache").await;
+
+        user_permission::Entity::delete_many()
+            .filter(user_permission::Column::UserId.eq(user.id))
+            .exec(&app_state.db)
+            .await
+            .unwrap();
+
+        user_permission::ActiveModel {
+            id: NotSet,
+            user_id: Set(user.id),
+            permission_id: Set(permission.id),
+        }
+        .insert(&app_state.db)
+        .await
+        .unwrap();
+
+        let access_token = access_token(&app_state, user.id).await;
+        let app = create_router(app_state).await;
+
+        let (status, first) = request(
+            &app,
+            http::Method::GET,
+            "/api/permissions/cache",
+            Some(&access_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // the permission check of the first request filled the cache, the second one reads it
+        let (status, second) = request(
+            &app,
+            http::Method::GET,
+            "/api/permissions/cache",
+            Some(&access_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        assert!(first["data"]["misses"].as_u64().unwrap() > 0);
+        assert_eq!(
+            second["data"]["hits"].as_u64().unwrap(),
+            first["data"]["hits"].as_u64().unwrap() + 1
+        );
+        assert_eq!(second["data"]["misses"], first["data"]["misses"]);
+    }
+}
@@ -1,42 +1,64 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use axum::{
-    Extension, Router,
     extract::{Path, State},
     response::IntoResponse,
-    routing::get,
 };
 use garde::Validate as _;
-use sea_orm::{ActiveModelTrait, DbErr, EntityTrait, IntoActiveModel, Set};
-use validator::{Validate, ValidateArgs};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::NotSet, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    IntoActiveModel, ModelTrait, QueryFilter, Set, TransactionTrait,
+};
 
 use crate::{
     AppState,
     api_response::JsonResponse,
-    auth::auth_service::AuthService,
+    auth::{
+        guarded_router::{GuardedRouter, delete, get, post},
+        role_hierarchy::RoleHierarchy,
+        token_store::TokenStore,
+    },
     error::AppError,
     extractor::ValidJson,
-    form::role_form::{CreateRoleRequest, UpdateRoleRequest},
-    models::_entities::{role, user},
-    serializer::RoleSerializer,
+    form::role_form::{CreateRoleRequest, UpdateRolePermissionRequest, UpdateRoleRequest},
+    models::_entities::{permission, role, role_permission},
+    serializer::{PermissionSerializer, RolePermissionSerializer, RoleSerializer},
 };
 
-pub async fn get_routes() -> Router<Arc<AppState>> {
-    Router::new()
-        .route("/", get(get_roles).post(create_role))
+pub async fn get_routes() -> GuardedRouter {
+    GuardedRouter::new()
+        .route(
+            "/",
+            get("read_roles", get_roles).post("create_role", create_role),
+        )
         .route(
             "/{role_id}",
-            get(get_role).put(update_role).delete(delete_role),
+            get("read_role", get_role)
+                .put("update_role", update_role)
+                .delete("delete_role", delete_role),
+        )
+        .route(
+            "/{role_id}/permissions",
+            get("read_role_permissions", get_role_permissions)
+                .post("assign_role_permissions", assign_permissions),
+        )
+        .route(
+            "/{role_id}/permissions/sync",
+            post("sync_role_permissions", sync_permissions),
+        )
+        .route(
+            "/{role_id}/permissions/{permission_id}",
+            delete("delete_role_permission", remove_permission),
         )
 }
 
 #[axum::debug_handler]
 pub async fn get_roles(
     State(app_state): State<Arc<AppState>>,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "read_roles").await?;
-
     let roles: Vec<RoleSerializer> = role::Entity::find()
         .all(&app_state.db)
         .await?
@@ -50,13 +72,14 @@ pub async fn get_roles(
 #[axum::debug_handler]
 pub async fn create_role(
     State(app_state): State<Arc<AppState>>,
-    Extension(user_model): Extension<user::Model>,
     ValidJson(payload): ValidJson<CreateRoleRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "create_role").await?;
-
     payload.validate_with(&app_state)?;
 
+    if let Some(parent_id) = payload.parent_id {
+        ensure_role_exists(&app_state.db, parent_id).await?;
+    }
+
     let role: RoleSerializer = payload
         .into_active_model()
         .insert(&app_state.db)
@@ -69,10 +92,7 @@ pub async fn create_role(
 pub async fn get_role(
     State(app_state): State<Arc<AppState>>,
     Path(role_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "read_role").await?;
-
     let role_serializer: RoleSerializer = role::Entity::find_by_id(role_id)
         .one(&app_state.db)
         .await?
@@ -84,11 +104,8 @@ pub async fn get_role(
 pub async fn update_role(
     State(app_state): State<Arc<AppState>>,
     Path(role_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
     ValidJson(payload): ValidJson<UpdateRoleRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "update_role").await?;
-
     let role = role::Entity::find_by_id(role_id)
         .one(&app_state.db)
         .await?
@@ -96,29 +113,557 @@ pub async fn update_role(
 
     payload.validate()?;
 
+    let txn = app_state.db.begin().await?;
+
+    let role_hierarchy = RoleHierarchy::load(&txn).await?;
+
+    if let Some(parent_id) = payload.parent_id {
+        ensure_role_exists(&txn, parent_id).await?;
+
+        if role_hierarchy.would_cycle(role_id, parent_id) {
+            return Err(AppError::GenericError(
+                "A role can't inherit from itself or from a role inheriting from it.".to_string(),
+            ));
+        }
+    }
+
+    // leaving a parent drops what was inherited from it, taking a first one only adds
+    if role.parent_id.is_some() && role.parent_id != payload.parent_id {
+        TokenStore::bump_token_version_for_roles(&txn, role_hierarchy.inheriting(role_id)).await?;
+    }
+
     let mut role: role::ActiveModel = role.into();
 
     role.name = Set(payload.name);
+    role.parent_id = Set(payload.parent_id);
+
+    let role_serializer: RoleSerializer = role.update(&txn).await?.into();
+
+    txn.commit().await?;
 
-    let role_serializer: RoleSerializer = role.update(&app_state.db).await?.into();
+    app_state.permission_cache.invalidate_all();
 
     Ok(JsonResponse::data(role_serializer, None))
 }
 pub async fn delete_role(
     State(app_state): State<Arc<AppState>>,
     Path(role_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "delete_role").await?;
+    let txn = app_state.db.begin().await?;
 
-    let res = role::Entity::delete_by_id(role_id)
-        .exec(&app_state.db)
-        .await?;
+    let inheriting = RoleHierarchy::load(&txn).await?.inheriting(role_id);
+
+    // the memberships are gone with the role, so its members are signed out first
+    TokenStore::bump_token_version_for_roles(&txn, inheriting).await?;
+
+    let res = role::Entity::delete_by_id(role_id).exec(&txn).await?;
+
+    txn.commit().await?;
 
     tracing::info!("{:?}", res);
 
+    if res.rows_affected > 0 {
+        app_state.permission_cache.invalidate_all();
+    }
+
     Ok(JsonResponse::data(
         None::<String>,
         Some("Role deleted successfully".to_string()),
     ))
 }
+
+#[axum::debug_handler]
+pub async fn get_role_permissions(
+    State(app_state): State<Arc<AppState>>,
+    Path(role_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let role = role::Entity::find_by_id(role_id)
+        .one(&app_state.db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("Role not found.".to_string()))?;
+
+    // the role itself first, then the roles it inherits from, nearest first
+    let role_ids = RoleHierarchy::load(&app_state.db)
+        .await?
+        .inherited([role.id]);
+
+    let roles: HashMap<i32, role::Model> = role::Entity::find()
+        .filter(role::Column::Id.is_in(role_ids.clone()))
+        .all(&app_state.db)
+        .await?
+        .into_iter()
+        .map(|role| (role.id, role))
+        .collect();
+
+    let mut granted: Vec<(role_permission::Model, Option<permission::Model>)> =
+        role_permission::Entity::find()
+            .filter(role_permission::Column::RoleId.is_in(role_ids.clone()))
+            .find_also_related(permission::Entity)
+            .all(&app_state.db)
+            .await?;
+
+    granted.sort_by_key(|(role_permission, _)| {
+        role_ids
+            .iter()
+            .position(|role_id| *role_id == role_permission.role_id)
+    });
+
+    // a permission the role holds itself isn't listed again as inherited
+    let mut listed = HashSet::new();
+
+    let permission_serializer: Vec<RolePermissionSerializer> = granted
+        .into_iter()
+        .filter_map(|(role_permission, permission)| {
+            let permission = permission.filter(|permission| listed.insert(permission.id))?;
+
+            let inherited_from = roles
+                .get(&role_permission.role_id)
+                .filter(|inherited| inherited.id != role.id)
+                .cloned()
+                .map(RoleSerializer::from);
+
+            Some(RolePermissionSerializer {
+                permission: permission.into(),
+                inherited_from,
+            })
+        })
+        .collect();
+
+    Ok(JsonResponse::data(permission_serializer, None))
+}
+
+/// Grants the role the given permissions, on top of the ones it already has.
+#[axum::debug_handler]
+pub async fn assign_permissions(
+    State(app_state): State<Arc<AppState>>,
+    Path(role_id): Path<i32>,
+    ValidJson(payload): ValidJson<UpdateRolePermissionRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let role = role::Entity::find_by_id(role_id)
+        .one(&app_state.db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("Role not found.".to_string()))?;
+
+    if payload.permissions.is_empty() {
+        return Err(AppError::GenericError("Empty permission.".to_string()));
+    }
+
+    let role_permissions: HashSet<i32> = role
+        .find_related(permission::Entity)
+        .all(&app_state.db)
+        .await?
+        .into_iter()
+        .map(|permission| permission.id)
+        .collect();
+
+    let new_permissions: Vec<permission::Model> = permission::Entity::find()
+        .filter(permission::Column::CodeName.is_in(&payload.permissions))
+        .all(&app_state.db)
+        .await?
+        .into_iter()
+        .filter(|permission| !role_permissions.contains(&permission.id))
+        .collect();
+
+    if new_permissions.is_empty() {
+        return Ok(JsonResponse::data(
+            Vec::<PermissionSerializer>::new(),
+            Some("Already added.".to_string()),
+        ));
+    }
+
+    let new_role_permissions: Vec<role_permission::ActiveModel> = new_permissions
+        .iter()
+        .map(|permission| role_permission::ActiveModel {
+            id: NotSet,
+            role_id: Set(role_id),
+            permission_id: Set(permission.id),
+        })
+        .collect();
+
+    role_permission::Entity::insert_many(new_role_permissions)
+        .exec(&app_state.db)
+        .await?;
+
+    app_state.permission_cache.invalidate_all();
+
+    let permission_serializer: Vec<PermissionSerializer> = new_permissions
+        .into_iter()
+        .map(PermissionSerializer::from)
+        .collect();
+
+    Ok(JsonResponse::data(
+        permission_serializer,
+        Some("Permissions added successfully".to_string()),
+    ))
+}
+
+/// Replaces the role's permissions with the given ones, unknown code names are ignored.
+#[axum::debug_handler]
+pub async fn sync_permissions(
+    State(app_state): State<Arc<AppState>>,
+    Path(role_id): Path<i32>,
+    ValidJson(payload): ValidJson<UpdateRolePermissionRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let role = role::Entity::find_by_id(role_id)
+        .one(&app_state.db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("Role not found.".to_string()))?;
+
+    let valid_permissions: HashSet<i32> = permission::Entity::find()
+        .filter(permission::Column::CodeName.is_in(&payload.permissions))
+        .all(&app_state.db)
+        .await?
+        .into_iter()
+        .map(|permission| permission.id)
+        .collect();
+
+    let role_permissions: HashSet<i32> = role
+        .find_related(permission::Entity)
+        .all(&app_state.db)
+        .await?
+        .into_iter()
+        .map(|permission| permission.id)
+        .collect();
+
+    let permissions_to_add: Vec<role_permission::ActiveModel> = valid_permissions
+        .difference(&role_permissions)
+        .map(|permission_id| role_permission::ActiveModel {
+            id: NotSet,
+            role_id: Set(role_id),
+            permission_id: Set(*permission_id),
+        })
+        .collect();
+
+    let permissions_to_delete: Vec<i32> = role_permissions
+        .difference(&valid_permissions)
+        .copied()
+        .collect();
+
+    if permissions_to_add.is_empty() && permissions_to_delete.is_empty() {
+        return Ok(JsonResponse::data(
+            None::<String>,
+            Some("No changes needed.".to_string()),
+        ));
+    }
+
+    let txn = app_state.db.begin().await?;
+
+    if !permissions_to_add.is_empty() {
+        role_permission::Entity::insert_many(permissions_to_add)
+            .exec(&txn)
+            .await?;
+    }
+
+    if !permissions_to_delete.is_empty() {
+        role_permission::Entity::delete_many()
+            .filter(role_permission::Column::RoleId.eq(role_id))
+            .filter(role_permission::Column::PermissionId.is_in(permissions_to_delete))
+            .exec(&txn)
+            .await?;
+
+        let inheriting = RoleHierarchy::load(&txn).await?.inheriting(role_id);
+
+        TokenStore::bump_token_version_for_roles(&txn, inheriting).await?;
+    }
+
+    txn.commit().await?;
+
+    app_state.permission_cache.invalidate_all();
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Permissions synced successfully.".to_string()),
+    ))
+}
+
+#[axum::debug_handler]
+pub async fn remove_permission(
+    State(app_state): State<Arc<AppState>>,
+    Path((role_id, permission_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse, AppError> {
+    let txn = app_state.db.begin().await?;
+
+    let res = role_permission::Entity::delete_many()
+        .filter(role_permission::Column::RoleId.eq(role_id))
+        .filter(role_permission::Column::PermissionId.eq(permission_id))
+        .exec(&txn)
+        .await?;
+
+    if res.rows_affected == 0 {
+        return Err(
+            DbErr::RecordNotFound("The role doesn't have this permission.".to_string()).into(),
+        );
+    }
+
+    let inheriting = RoleHierarchy::load(&txn).await?.inheriting(role_id);
+
+    TokenStore::bump_token_version_for_roles(&txn, inheriting).await?;
+
+    txn.commit().await?;
+
+    app_state.permission_cache.invalidate_all();
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Permission removed from the role".to_string()),
+    ))
+}
+
+async fn ensure_role_exists<C: ConnectionTrait>(db: &C, role_id: i32) -> Result<(), AppError> {
+    role::Entity::find_by_id(role_id)
+        .one(db)
+        .await?
+        .ok_or(AppError::GenericError("Parent role not found.".to_string()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{self, StatusCode};
+    use dotenvy::dotenv;
+    use sea_orm::{
+        ActiveModelTrait as _, ActiveValue::NotSet, ColumnTrait as _, EntityTrait as _,
+        QueryFilter as _, Set,
+    };
+    use serde_json::{Value, json};
+
+    use crate::{
+        models::_entities::{role_permission, user_role},
+        routes::create_router,
+        test_utils::{
+            access_token, find_or_create_permission, find_or_create_role, find_or_create_user,
+            request, test_state,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_role_permissions_are_granted_to_members() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let admin = find_or_create_user(&app_state, "role-permission-admin", true).await;
+        let member = find_or_create_user(&app_state, "role-permission-member", false).await;
+
+        let role = find_or_create_role(&app_state, "role-permission-editor").await;
+        let permission = find_or_create_permission(&app_state, "read_roles").await;
+
+        role_permission::Entity::delete_many()
+            .filter(role_permission::Column::RoleId.eq(role.id))
+            .exec(&app_state.db)
+            .await
+            .unwrap();
+
+        user_role::Entity::delete_many()
+            .filter(user_role::Column::UserId.eq(member.id))
+            .exec(&app_state.db)
+            .await
+            .unwrap();
+
+        user_role::ActiveModel {
+            id: NotSet,
+            user_id: Set(member.id),
+            role_id: Set(role.id),
+        }
+        .insert(&app_state.db)
+        .await
+        .unwrap();
+
+        let admin_token = access_token(&app_state, admin.id).await;
+        let member_token = access_token(&app_state, member.id).await;
+
+        let app = create_router(app_state.clone()).await;
+
+        let (status, _) = request(
+            &app,
+            http::Method::GET,
+            "/api/roles",
+            Some(&member_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = request(
+            &app,
+            http::Method::POST,
+            &format!("/api/roles/{}/permissions", role.id),
+            Some(&admin_token),
+            json!({ "permissions": ["read_roles"] }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["code_name"], "read_roles");
+
+        // members keep their sessions and hold the permission right away
+        let (status, _) = request(
+            &app,
+            http::Method::GET,
+            "/api/roles",
+            Some(&member_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = request(
+            &app,
+            http::Method::DELETE,
+            &format!("/api/roles/{}/permissions/{}", role.id, permission.id),
+            Some(&admin_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // losing the permission ends the members' sessions
+        let (status, _) = request(
+            &app,
+            http::Method::GET,
+            "/api/roles",
+            Some(&member_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_roles_inherit_permissions_of_their_parents() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let admin = find_or_create_user(&app_state, "role-hierarchy-admin", true).await;
+        let member = find_or_create_user(&app_state, "role-hierarchy-member", false).await;
+
+        let viewer = find_or_create_role(&app_state, "role-hierarchy-viewer").await;
+        let editor = find_or_create_role(&app_state, "role-hierarchy-editor").await;
+        let permission = find_or_create_permission(&app_state, "read_roles").await;
+
+        role_permission::Entity::delete_many()
+            .filter(role_permission::Column::RoleId.is_in([viewer.id, editor.id]))
+            .exec(&app_state.db)
+            .await
+            .unwrap();
+
+        role_permission::ActiveModel {
+            id: NotSet,
+            role_id: Set(viewer.id),
+            permission_id: Set(permission.id),
+        }
+        .insert(&app_state.db)
+        .await
+        .unwrap();
+
+        user_role::Entity::delete_many()
+            .filter(user_role::Column::UserId.eq(member.id))
+            .exec(&app_state.db)
+            .await
+            .unwrap();
+
+        user_role::ActiveModel {
+            id: NotSet,
+            user_id: Set(member.id),
+            role_id: Set(editor.id),
+        }
+        .insert(&app_state.db)
+        .await
+        .unwrap();
+
+        let admin_token = access_token(&app_state, admin.id).await;
+        let member_token = access_token(&app_state, member.id).await;
+
+        let app = create_router(app_state.clone()).await;
+
+        let (status, _) = request(
+            &app,
+            http::Method::GET,
+            "/api/roles",
+            Some(&member_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = request(
+            &app,
+            http::Method::PUT,
+            &format!("/api/roles/{}", editor.id),
+            Some(&admin_token),
+            json!({ "name": editor.name, "parent_id": viewer.id }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["parent_id"], viewer.id);
+
+        // the member's session carries on with the inherited permission
+        let (status, _) = request(
+            &app,
+            http::Method::GET,
+            "/api/roles",
+            Some(&member_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = request(
+            &app,
+            http::Method::GET,
+            &format!("/api/roles/{}/permissions", editor.id),
+            Some(&admin_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["code_name"], "read_roles");
+        assert_eq!(body["data"][0]["inherited_from"]["id"], viewer.id);
+
+        let (status, body) = request(
+            &app,
+            http::Method::GET,
+            &format!("/api/roles/{}/permissions", viewer.id),
+            Some(&admin_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["inherited_from"], Value::Null);
+
+        // neither a role nor its parents may inherit from it
+        for (role, parent_id) in [(&viewer, editor.id), (&editor, editor.id)] {
+            let (status, _) = request(
+                &app,
+                http::Method::PUT,
+                &format!("/api/roles/{}", role.id),
+                Some(&admin_token),
+                json!({ "name": role.name, "parent_id": parent_id }),
+            )
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+
+        let (status, _) = request(
+            &app,
+            http::Method::PUT,
+            &format!("/api/roles/{}", editor.id),
+            Some(&admin_token),
+            json!({ "name": editor.name, "parent_id": null }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // leaving the parent ends the sessions of the role's members
+        let (status, _) = request(
+            &app,
+            http::Method::GET,
+            "/api/roles",
+            Some(&member_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
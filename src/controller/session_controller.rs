@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Router,
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{delete, get},
+};
+
+use crate::{
+    AppState,
+    api_response::JsonResponse,
+    auth::{jwt::TokenClaims, token_store::TokenStore},
+    error::AppError,
+    models::_entities::user,
+    serializer::SessionSerializer,
+};
+
+pub async fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_sessions))
+        .route("/{session_id}", delete(delete_session))
+}
+
+#[axum::debug_handler]
+pub async fn get_sessions(
+    State(app_state): State<Arc<AppState>>,
+    Extension(user_model): Extension<user::Model>,
+    Extension(token_claims): Extension<TokenClaims>,
+) -> Result<impl IntoResponse, AppError> {
+    let sessions: Vec<SessionSerializer> =
+        TokenStore::active_sessions(&app_state.db, &app_state.config, user_model.id)
+            .await?
+            .into_iter()
+            .map(|session| SessionSerializer::new(session, token_claims.fam))
+            .collect();
+
+    Ok(JsonResponse::data(sessions, None))
+}
+
+#[axum::debug_handler]
+pub async fn delete_session(
+    State(app_state): State<Arc<AppState>>,
+    Path(session_id): Path<i32>,
+    Extension(user_model): Extension<user::Model>,
+) -> Result<impl IntoResponse, AppError> {
+    TokenStore::revoke_session(&app_state.db, user_model.id, session_id).await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Session signed out successfully.".to_string()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{self, StatusCode};
+    use dotenvy::dotenv;
+    use serde_json::Value;
+
+    use crate::{
+        auth::{
+            jwt::TokenType,
+            token_store::{SessionDevice, TokenStore},
+        },
+        routes::create_router,
+        test_utils::{access_token, find_or_create_user, request, test_state},
+    };
+
+    #[tokio::test]
+    async fn test_list_and_revoke_sessions() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+
+        let user = find_or_create_user(&app_state, "session-user", false).await;
+        let laptop_token = access_token(&app_state, user.id).await;
+
+        let phone_device = SessionDevice {
+            user_agent: Some("phone".to_string()),
+            ip_address: Some([10, 0, 0, 7].into()),
+        };
+        let phone_token = TokenStore::issue_token_pair(&app_state, &user, phone_device)
+            .await
+            .unwrap()
+            .access_token;
+        let phone_session_id = app_state
+            .jwt_keys
+            .decode(&phone_token, TokenType::Access)
+            .unwrap()
+            .fam
+            .unwrap();
+
+        let app = create_router(app_state).await;
+
+        let (status, body) = request(
+            &app,
+            http::Method::GET,
+            "/api/auth/sessions",
+            Some(&laptop_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let sessions = body["data"].as_array().unwrap();
+        let phone_session = sessions
+            .iter()
+            .find(|session| session["id"] == phone_session_id)
+            .unwrap();
+
+        assert_eq!(phone_session["user_agent"], "phone");
+        assert_eq!(phone_session["ip_address"], "10.0.0.7");
+        assert_eq!(phone_session["current"], false);
+        assert_eq!(
+            sessions
+                .iter()
+                .filter(|session| session["current"] == true)
+                .count(),
+            1
+        );
+
+        let uri = format!("/api/auth/sessions/{phone_session_id}");
+
+        let (status, _) = request(
+            &app,
+            http::Method::DELETE,
+            &uri,
+            Some(&laptop_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // tokens of the revoked session no longer authenticate
+        let (status, _) = request(
+            &app,
+            http::Method::GET,
+            "/api/auth/sessions",
+            Some(&phone_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = request(
+            &app,
+            http::Method::DELETE,
+            &uri,
+            Some(&laptop_token),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,308 @@
+use std::sync::Arc;
+
+use axum::{Extension, Router, extract::State, response::IntoResponse, routing::post};
+use axum_extra::extract::cookie::CookieJar;
+use garde::Validate as _;
+
+use crate::{
+    AppState,
+    api_response::JsonResponse,
+    auth::{
+        auth_cookies::AuthCookies,
+        auth_service::AuthUser,
+        identifier::Identifier,
+        impersonation::Impersonation,
+        jwt::TokenType,
+        login_throttle::LoginGuard,
+        token_store::{SessionDevice, TokenStore},
+        two_factor::TwoFactor,
+    },
+    error::AppError,
+    extractor::{ClientIp, UserAgent, ValidJson},
+    form::two_factor_form::{TwoFactorCodeRequest, TwoFactorVerifyRequest},
+};
+
+pub async fn get_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/enroll", post(enroll))
+        .route("/confirm", post(confirm))
+        .route("/disable", post(disable))
+}
+
+pub async fn get_public_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/verify", post(verify))
+}
+
+#[axum::debug_handler]
+pub async fn enroll(
+    State(app_state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+) -> Result<impl IntoResponse, AppError> {
+    Impersonation::ensure_not_impersonating(&auth_user)?;
+
+    let enrollment = TwoFactor::enroll(&app_state, &auth_user).await?;
+
+    Ok(JsonResponse::data(
+        enrollment,
+        Some("Confirm the enrollment with a code from your authenticator app.".to_string()),
+    ))
+}
+
+#[axum::debug_handler]
+pub async fn confirm(
+    State(app_state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    ValidJson(payload): ValidJson<TwoFactorCodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    Impersonation::ensure_not_impersonating(&auth_user)?;
+
+    TwoFactor::confirm(&app_state.db, &auth_user, &payload.code).await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Two-factor authentication enabled.".to_string()),
+    ))
+}
+
+#[axum::debug_handler]
+pub async fn disable(
+    State(app_state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    ValidJson(payload): ValidJson<TwoFactorCodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    Impersonation::ensure_not_impersonating(&auth_user)?;
+
+    TwoFactor::disable(&app_state.db, &auth_user, &payload.code).await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Two-factor authentication disabled.".to_string()),
+    ))
+}
+
+/// Exchanges the challenge token returned by `login` plus a TOTP or recovery code for a
+/// token pair.
+#[axum::debug_handler]
+pub async fn verify(
+    State(app_state): State<Arc<AppState>>,
+    ClientIp(client_ip): ClientIp,
+    UserAgent(user_agent): UserAgent,
+    jar: CookieJar,
+    ValidJson(payload): ValidJson<TwoFactorVerifyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let token_claims = app_state
+        .jwt_keys
+        .decode(&payload.challenge_token, TokenType::TwoFactorChallenge)?;
+
+    let user = TokenStore::authenticate(&app_state.db, &token_claims).await?;
+
+    if user.totp_enabled_at.is_none() {
+        return Err(AppError::InvalidToken);
+    }
+
+    // wrong codes count towards the same lockout as wrong passwords
+    let login_key = Identifier::login_key(&user.username);
+
+    LoginGuard::check(&app_state, client_ip, &login_key, Some(&user))?;
+
+    if !TwoFactor::verify_code(&app_state.db, &user, &payload.code).await? {
+        let user_id = user.id;
+
+        return match LoginGuard::register_failure(&app_state, client_ip, &login_key, Some(user))
+            .await
+        {
+            AppError::InvalidCredentials => Err(AppError::InvalidTwoFactorCode),
+            e => {
+                // a locked out challenge can't be retried once the lockout ends
+                TokenStore::revoke(&app_state.db, &token_claims, user_id).await?;
+
+                Err(e)
+            }
+        };
+    }
+
+    // a challenge is only good for one sign-in
+    TokenStore::revoke(&app_state.db, &token_claims, user.id).await?;
+
+    let user = LoginGuard::register_success(&app_state, user).await?;
+
+    let device = SessionDevice {
+        user_agent,
+        ip_address: client_ip,
+    };
+
+    let user_token = TokenStore::issue_token_pair(&app_state, &user, device).await?;
+
+    Ok(AuthCookies::token_response(
+        &app_state.config,
+        jar,
+        user_token,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{self, StatusCode};
+    use chrono::Utc;
+    use dotenvy::dotenv;
+    use sea_orm::{ActiveModelTrait as _, Set};
+    use serde_json::json;
+
+    use crate::{
+        auth::totp::Totp,
+        models::_entities::user,
+        routes::create_router,
+        test_utils::{access_token, request, test_state, user_with_password},
+    };
+
+    #[tokio::test]
+    async fn test_login_with_two_factor_challenge() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        let user = user_with_password(&app_state, "two-factor-user", "correct-horse").await;
+        let access_token = access_token(&app_state, user.id).await;
+
+        let app = create_router(app_state).await;
+
+        let (status, body) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/2fa/enroll",
+            Some(&access_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let totp = Totp::from_base32(body["data"]["secret"].as_str().unwrap()).unwrap();
+        let recovery_code = body["data"]["recovery_codes"][0]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let code = totp.code_at(Utc::now().timestamp() / 30);
+
+        let (status, _) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/2fa/confirm",
+            Some(&access_token),
+            json!({ "code": code }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/login",
+            None,
+            json!({ "username": "two-factor-user", "password": "correct-horse" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["data"]["access_token"].is_null());
+
+        let challenge_token = body["data"]["challenge_token"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        // the code used to confirm the enrollment can't be replayed
+        let (status, _) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/2fa/verify",
+            None,
+            json!({ "challenge_token": challenge_token, "code": code }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, body) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/2fa/verify",
+            None,
+            json!({ "challenge_token": challenge_token, "code": recovery_code }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["data"]["access_token"].is_string());
+
+        // neither the challenge nor the recovery code can be used twice
+        let (status, _) = request(
+            &app,
+            http::Method::POST,
+            "/api/auth/2fa/verify",
+            None,
+            json!({ "challenge_token": challenge_token, "code": recovery_code }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_two_factor_codes_lock_the_account() {
+        dotenv().ok();
+
+        let app_state = test_state().await;
+        let totp = Totp::generate();
+
+        let mut user: user::ActiveModel =
+            user_with_password(&app_state, "two-factor-guessed-user", "correct-horse")
+                .await
+                .into();
+        user.totp_secret = Set(Some(totp.to_base32()));
+        user.totp_enabled_at = Set(Some(Utc::now().naive_utc()));
+        user.update(&app_state.db).await.unwrap();
+
+        let max_attempts = app_state.config.login_max_attempts;
+        let app = create_router(app_state).await;
+
+        let login = json!({ "username": "two-factor-guessed-user", "password": "correct-horse" });
+
+        let mut statuses = Vec::new();
+
+        for _ in 0..max_attempts {
+            // a fresh challenge doesn't clear the failures of the previous ones
+            let (status, body) = request(
+                &app,
+                http::Method::POST,
+                "/api/auth/login",
+                None,
+                login.clone(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+
+            let challenge_token = body["data"]["challenge_token"].as_str().unwrap();
+
+            let (status, _) = request(
+                &app,
+                http::Method::POST,
+                "/api/auth/2fa/verify",
+                None,
+                json!({ "challenge_token": challenge_token, "code": "000000" }),
+            )
+            .await;
+            statuses.push(status);
+        }
+
+        assert_eq!(statuses.last(), Some(&StatusCode::TOO_MANY_REQUESTS));
+        assert!(
+            statuses[..statuses.len() - 1]
+                .iter()
+                .all(|status| *status == StatusCode::UNAUTHORIZED)
+        );
+
+        let (status, _) = request(&app, http::Method::POST, "/api/auth/login", None, login).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -2,12 +2,9 @@ use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use axum::Extension;
-use axum::routing::delete;
 use axum::{
-    Router,
     extract::{OriginalUri, Path, Query, State},
     response::IntoResponse,
-    routing::{get, post},
 };
 use garde::Validate as _;
 use sea_orm::Condition;
@@ -18,7 +15,15 @@ use sea_orm::{
 
 use crate::AppState;
 use crate::api_response::JsonResponse;
-use crate::auth::auth_service::AuthService;
+use crate::auth::auth_service::AuthUser;
+use crate::auth::deactivation::Deactivation;
+use crate::auth::email_change::EmailChange;
+use crate::auth::guarded_router::{GuardedRouter, delete, get, post};
+use crate::auth::identifier::Identifier;
+use crate::auth::impersonation::Impersonation;
+use crate::auth::password::PasswordHasher;
+use crate::auth::password_policy::PasswordHistory;
+use crate::auth::token_store::TokenStore;
 use crate::error::AppError;
 use crate::extractor::ValidJson;
 use crate::form::{
@@ -28,26 +33,58 @@ use crate::form::{
 use crate::models::_entities::{permission, role, user, user_permission, user_profile, user_role};
 use crate::repository::user_repository::UserRepository;
 use crate::serializer::{
-    PermissionSerializer, RoleSerializer, UserSerializer, UserWithProfileSerializer,
+    PermissionSerializer, RoleSerializer, SessionSerializer, UserSerializer,
+    UserWithProfileSerializer,
 };
 use crate::service::service_trait::ServiceTrait;
 use crate::service::user_service::UserService;
 
-pub async fn get_routes() -> Router<Arc<AppState>> {
-    Router::new()
-        .route("/", get(get_users).post(create_user))
+pub async fn get_routes() -> GuardedRouter {
+    GuardedRouter::new()
+        .route(
+            "/",
+            get("read_users", get_users).post("create_user", create_user),
+        )
         .route(
             "/{user_id}",
-            get(get_user).put(update_user).delete(delete_user),
+            get("read_user", get_user)
+                .put("update_user", update_user)
+                .delete("delete_user", delete_user),
+        )
+        .route(
+            "/{user_id}/deactivate",
+            post("deactivate_user", deactivate_user),
+        )
+        .route(
+            "/{user_id}/reactivate",
+            post("reactivate_user", reactivate_user),
+        )
+        .route(
+            "/{user_id}/roles",
+            get("read_user_roles", get_user_roles).post("assign_roles", assign_roles),
+        )
+        .route("/{user_id}/roles/sync", post("sync_roles", sync_roles))
+        .route(
+            "/{user_id}/roles/{role_id}",
+            delete("delete_user_role", delete_role),
         )
-        .route("/{user_id}/roles", get(get_user_roles).post(assign_roles))
-        .route("/{user_id}/roles/sync", post(sync_roles))
-        .route("/{user_id}/roles/{role_id}", delete(delete_role))
         .route(
             "/{user_id}/permissions",
-            get(get_user_permissions).post(assign_permissions),
+            get("read_user_permissions", get_user_permissions)
+                .post("assign_permissions", assign_permissions),
+        )
+        .route(
+            "/{user_id}/permissions/sync",
+            post("sync_permissions", sync_permissions),
+        )
+        .route(
+            "/{user_id}/sessions",
+            get("read_user_sessions", get_user_sessions),
+        )
+        .route(
+            "/{user_id}/sessions/{session_id}",
+            delete("revoke_user_sessions", delete_user_session),
         )
-        .route("/{user_id}/permissions/sync", post(sync_permissions))
 }
 
 #[axum::debug_handler()]
@@ -55,10 +92,7 @@ pub async fn get_users(
     State(app_state): State<Arc<AppState>>,
     Query(params): Query<HashMap<String, String>>,
     OriginalUri(original_uri): OriginalUri,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "read_users").await?;
-
     let user_repo = UserRepository::new(app_state.clone(), Some(original_uri.to_string()));
     let user_service = UserService::new(&user_repo);
 
@@ -77,10 +111,7 @@ pub async fn get_users(
 pub async fn get_user(
     State(app_state): State<Arc<AppState>>,
     Path(user_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "read_user").await?;
-
     let user_repo = UserRepository::new(app_state.clone(), None);
     let user_service = UserService::new(&user_repo);
 
@@ -92,11 +123,8 @@ pub async fn get_user(
 #[axum::debug_handler]
 pub async fn create_user(
     State(app_state): State<Arc<AppState>>,
-    Extension(user_model): Extension<user::Model>,
     ValidJson(payload): ValidJson<CreateUserRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "create_user").await?;
-
     payload.validate_with(&app_state)?;
 
     // let user_repo = UserRepository::new(app_state.clone(), None);
@@ -106,8 +134,8 @@ pub async fn create_user(
     let user_exist = user::Entity::find()
         .filter(
             Condition::any()
-                .add(user::Column::Email.eq(&payload.email))
-                .add(user::Column::Username.eq(&payload.username)),
+                .add(Identifier::email_eq(&payload.email))
+                .add(Identifier::username_eq(&payload.username)),
         )
         .one(&app_state.db)
         .await?;
@@ -118,11 +146,20 @@ pub async fn create_user(
         ));
     }
 
+    let password_hash = PasswordHasher::new(&app_state.config)?
+        .hash(&payload.password)
+        .await?;
+
     let user_with_profile = app_state
         .db
         .transaction::<_, (user::Model, Option<user_profile::Model>), DbErr>(|txn| {
             Box::pin(async move {
-                let user = user::ActiveModel::from(payload.clone()).insert(txn).await?;
+                let user = user::ActiveModel {
+                    password: Set(password_hash),
+                    ..payload.clone().into()
+                }
+                .insert(txn)
+                .await?;
 
                 let user_profile = user_profile::ActiveModel {
                     id: sea_orm::ActiveValue::NotSet,
@@ -148,63 +185,156 @@ pub async fn create_user(
 pub async fn update_user(
     State(app_state): State<Arc<AppState>>,
     Path(user_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
+    Extension(user_model): Extension<AuthUser>,
     ValidJson(payload): ValidJson<UpdateUserRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "update_user").await?;
-
     let user = user::Entity::find_by_id(user_id)
         .one(&app_state.db)
         .await?
         .ok_or(DbErr::RecordNotFound("User not found.".to_string()))?;
 
-    payload.validate()?;
+    payload.validate_with(&app_state)?;
 
-    let mut user: user::ActiveModel = user.into();
+    let username_taken = user::Entity::find()
+        .filter(Identifier::username_eq(&payload.username))
+        .filter(user::Column::Id.ne(user.id))
+        .one(&app_state.db)
+        .await?
+        .is_some();
+
+    if username_taken {
+        return Err(AppError::GenericError(
+            "A user with this username already exists.".to_string(),
+        ));
+    }
+
+    // the new address only replaces the current one once it is confirmed
+    let email_changed = Identifier::normalize_email(&payload.email) != user.email;
+
+    if email_changed {
+        Impersonation::ensure_not_impersonating(&user_model)?;
+
+        EmailChange::ensure_available(&app_state.db, &payload.email).await?;
+    }
+
+    let password_changed = payload.password.is_some();
+
+    if let Some(password) = &payload.password {
+        Impersonation::ensure_not_impersonating(&user_model)?;
+
+        PasswordHistory::ensure_not_reused(&app_state.db, &app_state.config, &user, password)
+            .await?;
+    }
 
     let password = match payload.password {
-        Some(pwd) => Set(pwd),
+        Some(pwd) => Set(PasswordHasher::new(&app_state.config)?.hash(&pwd).await?),
         None => NotSet,
     };
 
+    // the old password only goes to the history, and old sessions only end, with the new one
+    let txn = app_state.db.begin().await?;
+
+    if password_changed {
+        PasswordHistory::record(&txn, &app_state.config, &user).await?;
+    }
+
+    let mut user: user::ActiveModel = user.into();
+
     user.name = Set(payload.name);
     user.username = Set(payload.username);
-    user.email = Set(payload.email);
     user.password = password;
 
-    let user_serializer: UserSerializer = user.update(&app_state.db).await?.into();
+    let mut user = user.update(&txn).await?;
 
-    Ok(JsonResponse::data(user_serializer, None))
+    if password_changed {
+        TokenStore::revoke_all_for_user(&txn, user.id).await?;
+    }
+
+    txn.commit().await?;
+
+    let mut message = None;
+
+    if email_changed {
+        user = EmailChange::request(&app_state, user, &payload.email).await?;
+        message = Some("A confirmation link has been sent to the new email address.".to_string());
+    }
+
+    let user_serializer: UserSerializer = user.into();
+
+    Ok(JsonResponse::data(user_serializer, message))
 }
 
 #[axum::debug_handler()]
 pub async fn delete_user(
     State(app_state): State<Arc<AppState>>,
     Path(user_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "delete_user").await?;
-
     let res = user::Entity::delete_by_id(user_id)
         .exec(&app_state.db)
         .await?;
 
     println!("{:?}", res);
 
+    if res.rows_affected > 0 {
+        app_state.permission_cache.invalidate_user(user_id);
+    }
+
     Ok(JsonResponse::data(
         None::<String>,
         Some("User deleted successfully".to_string()),
     ))
 }
 
+/// Signs the user out everywhere and keeps them from signing in until they are reactivated.
 #[axum::debug_handler()]
-pub async fn get_user_roles(
+pub async fn deactivate_user(
+    State(app_state): State<Arc<AppState>>,
+    Path(user_id): Path<i32>,
+    Extension(auth_user): Extension<AuthUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = user::Entity::find_by_id(user_id)
+        .one(&app_state.db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("User not found.".to_string()))?;
+
+    if user.id == auth_user.id {
+        return Err(AppError::GenericError(
+            "You can't deactivate your own account.".to_string(),
+        ));
+    }
+
+    let user_serializer: UserSerializer = Deactivation::deactivate(&app_state, user).await?.into();
+
+    Ok(JsonResponse::data(
+        user_serializer,
+        Some("User deactivated successfully.".to_string()),
+    ))
+}
+
+#[axum::debug_handler()]
+pub async fn reactivate_user(
     State(app_state): State<Arc<AppState>>,
     Path(user_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "read_user_roles").await?;
+    let user = user::Entity::find_by_id(user_id)
+        .one(&app_state.db)
+        .await?
+        .ok_or(DbErr::RecordNotFound("User not found.".to_string()))?;
+
+    let user_serializer: UserSerializer =
+        Deactivation::reactivate(&app_state.db, user).await?.into();
+
+    Ok(JsonResponse::data(
+        user_serializer,
+        Some("User reactivated successfully.".to_string()),
+    ))
+}
 
+#[axum::debug_handler()]
+pub async fn get_user_roles(
+    State(app_state): State<Arc<AppState>>,
+    Path(user_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
     let user_with_roles = user::Entity::find_by_id(user_id)
         .find_with_related(role::Entity)
         .all(&app_state.db)
@@ -223,11 +353,8 @@ pub async fn get_user_roles(
 pub async fn assign_roles(
     State(app_state): State<Arc<AppState>>,
     Path(user_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
     ValidJson(payload): ValidJson<UpdateUserRolesRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "assign_roles").await?;
-
     let _user_model = user::Entity::find_by_id(user_id)
         .one(&app_state.db)
         .await?
@@ -279,6 +406,8 @@ pub async fn assign_roles(
         user_role::Entity::insert_many(user_roles)
             .exec(&app_state.db)
             .await?;
+
+        app_state.permission_cache.invalidate_user(user_id);
     }
 
     Ok(JsonResponse::data(
@@ -291,10 +420,7 @@ pub async fn assign_roles(
 pub async fn get_user_permissions(
     State(app_state): State<Arc<AppState>>,
     Path(user_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "read_user_permissions").await?;
-
     let user_with_permissions = user::Entity::find_by_id(user_id)
         .find_with_related(permission::Entity)
         .all(&app_state.db)
@@ -313,11 +439,8 @@ pub async fn get_user_permissions(
 pub async fn assign_permissions(
     State(app_state): State<Arc<AppState>>,
     Path(user_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
     ValidJson(payload): ValidJson<UpdateUserPermissionRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "assign_permissions").await?;
-
     let user_model = user::Entity::find_by_id(user_id)
         .one(&app_state.db)
         .await?
@@ -368,6 +491,8 @@ pub async fn assign_permissions(
         user_permission::Entity::insert_many(user_permissions)
             .exec(&app_state.db)
             .await?;
+
+        app_state.permission_cache.invalidate_user(user_id);
     }
 
     Ok(JsonResponse::data(
@@ -380,11 +505,8 @@ pub async fn assign_permissions(
 pub async fn sync_permissions(
     State(app_state): State<Arc<AppState>>,
     Path(user_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
     ValidJson(payload): ValidJson<UpdateUserPermissionRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "sync_permissions").await?;
-
     let _user_model = user::Entity::find_by_id(user_id)
         .one(&app_state.db)
         .await?
@@ -400,11 +522,16 @@ pub async fn sync_permissions(
 
     if valid_permissions.is_empty() {
         // delete all permissions of the user
-        let _res = user_permission::Entity::delete_many()
+        let res = user_permission::Entity::delete_many()
             .filter(user_permission::Column::UserId.eq(user_id))
             .exec(&app_state.db)
             .await?;
 
+        if res.rows_affected > 0 {
+            TokenStore::bump_token_version(&app_state.db, user_id).await?;
+            app_state.permission_cache.invalidate_user(user_id);
+        }
+
         return Ok(JsonResponse::data(
             None::<String>,
             Some("Permission synced successfully.".to_string()),
@@ -458,6 +585,9 @@ pub async fn sync_permissions(
         })
         .collect();
 
+    // removing permissions ends the user's sessions, adding them doesn't
+    let permissions_removed = !permissions_to_delete.is_empty();
+
     app_state
         .db
         .transaction::<_, (), DbErr>(|txn| {
@@ -482,6 +612,12 @@ pub async fn sync_permissions(
         .await
         .map_err(|e| AppError::GenericError(e.to_string()))?; // should be database error
 
+    if permissions_removed {
+        TokenStore::bump_token_version(&app_state.db, user_id).await?;
+    }
+
+    app_state.permission_cache.invalidate_user(user_id);
+
     Ok(JsonResponse::data(
         None::<String>,
         Some("Permissions sync successfully".to_string()),
@@ -492,11 +628,8 @@ pub async fn sync_permissions(
 pub async fn sync_roles(
     State(app_state): State<Arc<AppState>>,
     Path(user_id): Path<i32>,
-    Extension(user_model): Extension<user::Model>,
     ValidJson(payload): ValidJson<UpdateUserRolesRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "sync_roles").await?;
-
     let _user_model = user::Entity::find_by_id(user_id)
         .one(&app_state.db)
         .await?
@@ -512,11 +645,16 @@ pub async fn sync_roles(
 
     if valid_roles.is_empty() {
         // delete all roles of the user
-        let _res = user_role::Entity::delete_many()
+        let res = user_role::Entity::delete_many()
             .filter(user_role::Column::UserId.eq(user_id))
             .exec(&app_state.db)
             .await?;
 
+        if res.rows_affected > 0 {
+            TokenStore::bump_token_version(&app_state.db, user_id).await?;
+            app_state.permission_cache.invalidate_user(user_id);
+        }
+
         return Ok(JsonResponse::data(
             None::<String>,
             Some("Roles synced successfully.".to_string()),
@@ -567,6 +705,9 @@ pub async fn sync_roles(
         })
         .collect();
 
+    // removing roles ends the user's sessions, adding them doesn't
+    let roles_removed = !roles_to_delete.is_empty();
+
     app_state
         .db
         .transaction::<_, (), DbErr>(|txn| {
@@ -591,6 +732,12 @@ pub async fn sync_roles(
         .await
         .map_err(|e| AppError::GenericError(e.to_string()))?; // should be database error
 
+    if roles_removed {
+        TokenStore::bump_token_version(&app_state.db, user_id).await?;
+    }
+
+    app_state.permission_cache.invalidate_user(user_id);
+
     Ok(JsonResponse::data(
         None::<String>,
         Some("Roles sync successfully".to_string()),
@@ -601,10 +748,7 @@ pub async fn sync_roles(
 pub async fn delete_role(
     State(app_state): State<Arc<AppState>>,
     Path((user_id, role_id)): Path<(i32, i32)>,
-    Extension(user_model): Extension<user::Model>,
 ) -> Result<impl IntoResponse, AppError> {
-    AuthService::has_permission(&app_state, &user_model, "delete_user_role").await?;
-
     let _user_model = user::Entity::find_by_id(user_id)
         .one(&app_state.db)
         .await?
@@ -623,8 +767,41 @@ pub async fn delete_role(
 
     println!("{:?}", res);
 
+    if res.rows_affected > 0 {
+        TokenStore::bump_token_version(&app_state.db, user_id).await?;
+        app_state.permission_cache.invalidate_user(user_id);
+    }
+
     Ok(JsonResponse::data(
         None::<String>,
         Some("Role removed from the user".to_string()),
     ))
 }
+
+#[axum::debug_handler()]
+pub async fn get_user_sessions(
+    State(app_state): State<Arc<AppState>>,
+    Path(user_id): Path<i32>,
+) -> Result<impl IntoResponse, AppError> {
+    let sessions: Vec<SessionSerializer> =
+        TokenStore::active_sessions(&app_state.db, &app_state.config, user_id)
+            .await?
+            .into_iter()
+            .map(|session| SessionSerializer::new(session, None))
+            .collect();
+
+    Ok(JsonResponse::data(sessions, None))
+}
+
+#[axum::debug_handler()]
+pub async fn delete_user_session(
+    State(app_state): State<Arc<AppState>>,
+    Path((user_id, session_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse, AppError> {
+    TokenStore::revoke_session(&app_state.db, user_id, session_id).await?;
+
+    Ok(JsonResponse::data(
+        None::<String>,
+        Some("Session signed out successfully.".to_string()),
+    ))
+}
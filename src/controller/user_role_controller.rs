@@ -1,17 +1,18 @@
 use std::sync::Arc;
 
-use axum::{extract::State, response::IntoResponse, routing::get, Router};
+use axum::{extract::State, response::IntoResponse};
 use sea_orm::EntityTrait;
 
 use crate::{
+    AppState,
     api_response::JsonResponse,
+    auth::guarded_router::{GuardedRouter, get},
     error::AppError,
     models::_entities::{role, user},
-    AppState,
 };
 
-pub async fn get_routes() -> Router<Arc<AppState>> {
-    Router::new().route("/", get(get_user_roles))
+pub async fn get_routes() -> GuardedRouter {
+    GuardedRouter::new().route("/", get("read_user_roles", get_user_roles))
 }
 
 pub async fn get_user_roles(
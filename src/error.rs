@@ -3,6 +3,7 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use chrono::{DateTime, Utc};
 use sea_orm::error::DbErr;
 use serde_json::json;
 use std::collections::HashMap;
@@ -28,7 +29,7 @@ pub enum AppError {
     #[error("No Token Found.")]
     EmptyToken,
 
-    #[error("Token Expired")]
+    #[error("Invalid Token")]
     InvalidToken,
 
     #[error("Token Expired")]
@@ -36,6 +37,27 @@ pub enum AppError {
 
     #[error("Forbidden")]
     Forbidden,
+
+    #[error("Email address is not verified")]
+    EmailNotVerified,
+
+    #[error("Account is deactivated")]
+    AccountDeactivated,
+
+    #[error("Invalid two-factor authentication code")]
+    InvalidTwoFactorCode,
+
+    #[error("Invalid username or password")]
+    InvalidCredentials,
+
+    #[error("Too many failed login attempts, locked until {0}")]
+    TooManyLoginAttempts(DateTime<Utc>),
+
+    #[error("Missing or invalid CSRF token")]
+    InvalidCsrfToken,
+
+    #[error("Identity provider error: {0}")]
+    IdentityProviderError(String),
 }
 
 impl IntoResponse for AppError {
@@ -66,6 +88,44 @@ impl IntoResponse for AppError {
                 json!("You are not allowed to access the resource"),
                 "Forbidden Access".into(),
             ),
+            AppError::EmailNotVerified => (
+                StatusCode::FORBIDDEN,
+                json!("Please verify your email address first."),
+                "Email Not Verified".into(),
+            ),
+            AppError::AccountDeactivated => (
+                StatusCode::FORBIDDEN,
+                json!("This account has been deactivated."),
+                "Account Deactivated".into(),
+            ),
+            AppError::InvalidTwoFactorCode => (
+                StatusCode::UNAUTHORIZED,
+                json!("Invalid two-factor authentication code."),
+                "Authentication Error".into(),
+            ),
+            AppError::InvalidCredentials => (
+                StatusCode::UNAUTHORIZED,
+                json!("Invalid username or password."),
+                "Authentication Error".into(),
+            ),
+            AppError::TooManyLoginAttempts(locked_until) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                json!({ "locked_until": locked_until }),
+                "Too many failed login attempts.".into(),
+            ),
+            AppError::InvalidCsrfToken => (
+                StatusCode::FORBIDDEN,
+                json!("Missing or invalid CSRF token."),
+                "Forbidden Access".into(),
+            ),
+            AppError::IdentityProviderError(msg) => {
+                tracing::error!("Identity provider error: {}", msg);
+                (
+                    StatusCode::BAD_GATEWAY,
+                    json!("Sign in with the identity provider failed."),
+                    "Identity Provider Error".into(),
+                )
+            }
             AppError::Unauthorized => (
                 StatusCode::UNAUTHORIZED,
                 json!("Unauthorized access"),
@@ -1,10 +1,16 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
 use axum::{
-    extract::{rejection::JsonRejection, FromRequest, Request},
-    http::StatusCode,
     Json,
+    extract::{ConnectInfo, FromRequest, FromRequestParts, Request, rejection::JsonRejection},
+    http::{StatusCode, header, request::Parts},
 };
 
-use crate::api_response::JsonResponse;
+use crate::{AppState, api_response::JsonResponse};
 
 pub struct ValidJson<T>(pub T);
 
@@ -35,3 +41,58 @@ where
         }
     }
 }
+
+/// IP address of the client, `None` when the server runs without connect info, e.g. in tests.
+///
+/// With `trust_forwarded_for` enabled, the address appended to `X-Forwarded-For` by the reverse
+/// proxy is used instead of the address of the connection.
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl FromRequestParts<Arc<AppState>> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        if state.config.trust_forwarded_for {
+            let forwarded_ip = parts
+                .headers
+                .get("x-forwarded-for")
+                .and_then(|header| header.to_str().ok())
+                .and_then(|header| header.rsplit(',').next())
+                .and_then(|ip| ip.trim().parse().ok());
+
+            if forwarded_ip.is_some() {
+                return Ok(Self(forwarded_ip));
+            }
+        }
+
+        let connect_ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        Ok(Self(connect_ip))
+    }
+}
+
+/// `User-Agent` of the client, cut to a length that is reasonable to store.
+pub struct UserAgent(pub Option<String>);
+
+impl<S> FromRequestParts<S> for UserAgent
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|header| header.to_str().ok())
+            .map(|user_agent| user_agent.chars().take(512).collect());
+
+        Ok(Self(user_agent))
+    }
+}
@@ -0,0 +1,15 @@
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct CreateApiKeyRequest {
+    #[garde(length(min = 1, max = 100))]
+    pub name: String,
+
+    #[garde(skip)]
+    pub expires_at: Option<NaiveDateTime>,
+
+    /// Code names of the permissions the key is scoped to.
+    #[garde(length(max = 100), inner(length(min = 1, max = 50)))]
+    pub permissions: Vec<String>,
+}
@@ -0,0 +1,13 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct MagicLinkRequest {
+    #[garde(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct MagicLinkVerifyRequest {
+    #[garde(length(min = 1))]
+    pub token: String,
+}
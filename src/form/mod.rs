@@ -1,3 +1,7 @@
+pub mod api_key_form;
+pub mod magic_link_form;
+pub mod oauth_form;
 pub mod permission_form;
 pub mod role_form;
+pub mod two_factor_form;
 pub mod user_form;
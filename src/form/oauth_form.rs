@@ -0,0 +1,18 @@
+use serde::Deserialize;
+
+/// Query string the identity provider redirects back with.
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct OAuthCallbackRequest {
+    #[garde(length(min = 1))]
+    pub code: Option<String>,
+
+    #[garde(length(min = 1))]
+    pub state: Option<String>,
+
+    /// Set instead of `code` when the user denied access or the provider failed.
+    #[garde(skip)]
+    pub error: Option<String>,
+
+    #[garde(skip)]
+    pub error_description: Option<String>,
+}
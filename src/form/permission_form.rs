@@ -21,12 +21,3 @@ impl From<CreatePermissionRequest> for ActiveModel {
         }
     }
 }
-
-#[derive(Debug, Deserialize, garde::Validate, Clone, DeriveIntoActiveModel)]
-pub struct UpdatePermissionRequest {
-    #[garde(length(min = 5, max = 100))]
-    pub name: String,
-
-    #[garde(length(min = 3, max = 50))]
-    pub code_name: String,
-}
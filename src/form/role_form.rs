@@ -15,6 +15,9 @@ pub struct CreateRoleRequest {
     #[garde(length(min = 3, max = 100))]
     #[garde(custom(CreateRoleRequest::validate_role_exists))]
     pub name: String,
+    /// Role whose permissions this one inherits.
+    #[garde(skip)]
+    pub parent_id: Option<i32>,
 }
 
 impl CreateRoleRequest {
@@ -47,6 +50,7 @@ impl From<CreateRoleRequest> for ActiveModel {
     fn from(value: CreateRoleRequest) -> Self {
         Self {
             name: Set(value.name),
+            parent_id: Set(value.parent_id),
             ..Default::default()
         }
     }
@@ -56,6 +60,9 @@ impl From<CreateRoleRequest> for ActiveModel {
 pub struct UpdateRoleRequest {
     #[garde(length(min = 3, max = 100))]
     pub name: String,
+    /// Role whose permissions this one inherits, leaving it out removes the current parent.
+    #[garde(skip)]
+    pub parent_id: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, garde::Validate)]
@@ -69,3 +76,9 @@ pub struct UpdateUserPermissionRequest {
     #[garde(skip)]
     pub permissions: Vec<String>,
 }
+
+#[derive(Debug, Serialize, Deserialize, garde::Validate)]
+pub struct UpdateRolePermissionRequest {
+    #[garde(skip)]
+    pub permissions: Vec<String>,
+}
@@ -0,0 +1,17 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct TwoFactorCodeRequest {
+    /// A TOTP code, or a recovery code where the endpoint accepts one.
+    #[garde(length(min = 6, max = 20))]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct TwoFactorVerifyRequest {
+    #[garde(length(min = 1))]
+    pub challenge_token: String,
+
+    #[garde(length(min = 6, max = 20))]
+    pub code: String,
+}
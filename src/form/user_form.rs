@@ -1,9 +1,9 @@
 use crate::{
+    auth::{identifier::Identifier, password_policy::PasswordPolicy},
     models::_entities::user::{self, ActiveModel},
     state::AppState,
-    utils::hash,
 };
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set};
+use sea_orm::{EntityTrait, QueryFilter, Set};
 use std::sync::Arc;
 
 use serde::Deserialize;
@@ -15,6 +15,7 @@ pub struct CreateUserRequest {
     pub name: String,
 
     #[garde(length(min = 5, max = 100))]
+    #[garde(custom(Identifier::validate_username))]
     #[garde(custom(CreateUserRequest::validate_username_exists))]
     pub username: String,
 
@@ -22,7 +23,7 @@ pub struct CreateUserRequest {
     #[garde(custom(CreateUserRequest::validate_email_exists))]
     pub email: String,
 
-    #[garde(length(min = 8))]
+    #[garde(custom(PasswordPolicy::rule(&self.username, &self.email)))]
     pub password: String,
 
     #[garde(length(max = 200))]
@@ -37,7 +38,7 @@ impl CreateUserRequest {
         tokio::task::block_in_place(|| {
             tokio::runtime::Handle::current().block_on(async {
                 match user::Entity::find()
-                    .filter(user::Column::Username.eq(value))
+                    .filter(Identifier::username_eq(value))
                     .one(&context.db)
                     .await
                 {
@@ -59,7 +60,7 @@ impl CreateUserRequest {
         tokio::task::block_in_place(|| {
             tokio::runtime::Handle::current().block_on(async {
                 match user::Entity::find()
-                    .filter(user::Column::Email.eq(value))
+                    .filter(Identifier::email_eq(value))
                     .one(&context.db)
                     .await
                 {
@@ -80,48 +81,105 @@ impl CreateUserRequest {
     }
 }
 
+/// The password is left unset, callers store the hash produced by `PasswordHasher`.
 impl From<CreateUserRequest> for ActiveModel {
     fn from(value: CreateUserRequest) -> Self {
         Self {
             name: Set(value.name),
             username: Set(value.username),
             email: Set(value.email),
-            password: Set(hash(value.password.as_ref())),
             ..Default::default()
         }
     }
 }
 
 #[derive(Debug, Deserialize, garde::Validate)]
+#[garde(context(Arc<AppState>))]
 pub struct UpdateUserRequest {
     #[garde(length(min = 3, max = 100))]
     pub name: String,
 
     #[garde(length(min = 3, max = 100))]
+    #[garde(custom(Identifier::validate_username))]
     pub username: String,
 
     #[garde(email)]
     #[garde(length(min = 3, max = 100))]
     pub email: String,
 
-    #[garde(length(min = 8, max = 100))]
+    #[garde(inner(custom(PasswordPolicy::rule(&self.username, &self.email))))]
     pub password: Option<String>,
 }
 
 #[derive(Debug, Deserialize, garde::Validate)]
 pub struct UserLogin {
-    #[garde(length(min = 3, max = 100))]
+    /// Username or email address.
+    #[garde(length(min = 3, max = 254))]
     pub username: String,
 
-    #[garde(length(min = 8, max = 100))]
+    /// Not held to the password policy, which may have changed since the password was set.
+    #[garde(length(min = 1))]
     pub password: String,
 }
 
 #[derive(Debug, Deserialize, garde::Validate)]
-pub struct UserRegisterRequest {
+pub struct RefreshTokenRequest {
+    /// Read from the refresh token cookie when omitted in cookie session mode.
+    #[garde(length(min = 1))]
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct VerifyEmailRequest {
+    #[garde(length(min = 1))]
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct ForgotPasswordRequest {
+    #[garde(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct ResetPasswordRequest {
+    #[garde(length(min = 1))]
+    pub token: String,
+
+    /// Checked against the password policy once the token's user is known.
+    #[garde(length(min = 1))]
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct ResendVerificationEmailRequest {
+    #[garde(email)]
+    pub email: String,
+}
+
+/// Edits of the signed-in user's own account, fields left out are kept.
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct UpdateMeRequest {
     #[garde(length(min = 3, max = 100))]
-    pub username: String,
+    pub name: Option<String>,
+
+    #[garde(length(min = 5, max = 100))]
+    #[garde(inner(custom(Identifier::validate_username)))]
+    pub username: Option<String>,
+
+    #[garde(length(max = 200))]
+    pub address: Option<String>,
+
+    #[garde(length(max = 50))]
+    pub mobile_number: Option<String>,
+}
+
+#[derive(Debug, Deserialize, garde::Validate)]
+pub struct ChangePasswordRequest {
+    #[garde(length(min = 1))]
+    pub current_password: String,
 
-    #[garde(length(min = 8, max = 100))]
+    /// Checked against the password policy with the user's username and email.
+    #[garde(length(min = 1))]
     pub password: String,
 }
@@ -1,8 +1,7 @@
 use std::sync::Arc;
 
 use lettre::{
-    transport::smtp::authentication::Credentials,
-    Message, SmtpTransport, Transport as _,
+    Message, SmtpTransport, Transport as _, transport::smtp::authentication::Credentials,
 };
 use sailfish::TemplateSimple;
 
@@ -12,15 +11,150 @@ use crate::AppState;
 #[template(path = "user_register_email.stpl")]
 struct UserRegisterTemplate {
     username: String,
+    verification_url: String,
 }
 
-pub fn send_register_mail(app_state: Arc<AppState>, subject: &str, to: &str) -> Result<(), String> {
+#[derive(TemplateSimple)]
+#[template(path = "verify_email.stpl")]
+struct VerifyEmailTemplate {
+    username: String,
+    verification_url: String,
+}
+
+#[derive(TemplateSimple)]
+#[template(path = "password_reset_email.stpl")]
+struct PasswordResetTemplate {
+    username: String,
+    reset_token: String,
+    expire_in_minutes: i64,
+}
+
+#[derive(TemplateSimple)]
+#[template(path = "magic_link_email.stpl")]
+struct MagicLinkTemplate {
+    username: String,
+    login_url: String,
+    expire_in_minutes: i64,
+}
+
+#[derive(TemplateSimple)]
+#[template(path = "email_change_email.stpl")]
+struct EmailChangeTemplate {
+    username: String,
+    confirmation_url: String,
+}
+
+#[derive(TemplateSimple)]
+#[template(path = "email_change_notice_email.stpl")]
+struct EmailChangeNoticeTemplate {
+    username: String,
+    new_email: String,
+}
+
+pub fn send_register_mail(
+    app_state: Arc<AppState>,
+    subject: &str,
+    to: &str,
+    verification_url: &str,
+) -> Result<(), String> {
     let email_body = UserRegisterTemplate {
         username: to.to_string(),
+        verification_url: verification_url.to_string(),
+    }
+    .render_once()
+    .map_err(|e| e.to_string())?;
+
+    send_mail(&app_state, subject, to, email_body)
+}
+
+pub fn send_verification_mail(
+    app_state: Arc<AppState>,
+    subject: &str,
+    to: &str,
+    verification_url: &str,
+) -> Result<(), String> {
+    let email_body = VerifyEmailTemplate {
+        username: to.to_string(),
+        verification_url: verification_url.to_string(),
     }
     .render_once()
-    .unwrap();
+    .map_err(|e| e.to_string())?;
+
+    send_mail(&app_state, subject, to, email_body)
+}
+
+pub fn send_password_reset_mail(
+    app_state: Arc<AppState>,
+    subject: &str,
+    to: &str,
+    reset_token: &str,
+) -> Result<(), String> {
+    let email_body = PasswordResetTemplate {
+        username: to.to_string(),
+        reset_token: reset_token.to_string(),
+        expire_in_minutes: app_state.config.password_reset_expiration_minutes,
+    }
+    .render_once()
+    .map_err(|e| e.to_string())?;
+
+    send_mail(&app_state, subject, to, email_body)
+}
+
+pub fn send_magic_link_mail(
+    app_state: Arc<AppState>,
+    subject: &str,
+    to: &str,
+    login_url: &str,
+) -> Result<(), String> {
+    let email_body = MagicLinkTemplate {
+        username: to.to_string(),
+        login_url: login_url.to_string(),
+        expire_in_minutes: app_state.config.magic_link_expiration_minutes,
+    }
+    .render_once()
+    .map_err(|e| e.to_string())?;
+
+    send_mail(&app_state, subject, to, email_body)
+}
+
+pub fn send_email_change_mail(
+    app_state: Arc<AppState>,
+    subject: &str,
+    to: &str,
+    confirmation_url: &str,
+) -> Result<(), String> {
+    let email_body = EmailChangeTemplate {
+        username: to.to_string(),
+        confirmation_url: confirmation_url.to_string(),
+    }
+    .render_once()
+    .map_err(|e| e.to_string())?;
+
+    send_mail(&app_state, subject, to, email_body)
+}
+
+pub fn send_email_change_notice_mail(
+    app_state: Arc<AppState>,
+    subject: &str,
+    to: &str,
+    new_email: &str,
+) -> Result<(), String> {
+    let email_body = EmailChangeNoticeTemplate {
+        username: to.to_string(),
+        new_email: new_email.to_string(),
+    }
+    .render_once()
+    .map_err(|e| e.to_string())?;
+
+    send_mail(&app_state, subject, to, email_body)
+}
 
+fn send_mail(
+    app_state: &AppState,
+    subject: &str,
+    to: &str,
+    email_body: String,
+) -> Result<(), String> {
     let app_config = app_state.config.clone();
 
     let email = Message::builder()
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use configgg::AppConfig;
 use routes::create_router;
@@ -21,6 +21,8 @@ mod routes;
 mod serializer;
 mod service;
 mod state;
+#[cfg(test)]
+mod test_utils;
 mod utils;
 
 #[tokio::main]
@@ -45,10 +47,8 @@ async fn main() {
         .expect("Failed to connect to database");
 
     // Create application state
-    let app_state = Arc::new(AppState {
-        db,
-        config: app_config.clone(),
-    });
+    let app_state =
+        Arc::new(AppState::new(db, app_config.clone()).expect("Failed to load application state"));
 
     // Create the Axum router
     let app = create_router(app_state).await;
@@ -60,10 +60,14 @@ async fn main() {
 
     tracing::info!("Listening on {}", app_config.server_address);
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap();
+    // connect info provides the client IP used to throttle logins
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
 }
 
 async fn shutdown_signal() {
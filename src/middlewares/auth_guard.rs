@@ -2,30 +2,131 @@ use std::sync::Arc;
 
 use axum::{
     extract::{Request, State},
-    http::header,
+    http::{HeaderMap, header},
     middleware::Next,
     response::Response,
 };
 
-use crate::{error::AppError, utils::verify_token, AppState};
+use axum_extra::extract::cookie::CookieJar;
+
+use crate::{
+    AppState,
+    auth::{
+        api_key_store::ApiKeyStore,
+        auth_cookies::{ACCESS_TOKEN_COOKIE, AuthCookies},
+        auth_service::AuthUser,
+        impersonation::Impersonation,
+    },
+    error::AppError,
+    utils::verify_token,
+};
+
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// Credentials a request can authenticate with.
+#[derive(Clone, Copy)]
+enum Credentials<'a> {
+    Bearer(&'a str),
+    ApiKey(&'a str),
+    /// Access token cookie, only accepted in cookie session mode.
+    Cookie(&'a str),
+}
+
+fn credentials<'a>(
+    headers: &'a HeaderMap,
+    jar: &'a CookieJar,
+    accept_cookie: bool,
+) -> Option<Credentials<'a>> {
+    if let Some(api_key) = headers
+        .get(API_KEY_HEADER)
+        .and_then(|header| header.to_str().ok())
+    {
+        return Some(Credentials::ApiKey(api_key.trim()));
+    }
+
+    if let Some(authorization) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+    {
+        if let Some(token) = authorization.strip_prefix("Bearer ") {
+            return Some(Credentials::Bearer(token));
+        }
+
+        if let Some(api_key) = authorization.strip_prefix("ApiKey ") {
+            return Some(Credentials::ApiKey(api_key.trim()));
+        }
+    }
+
+    jar.get(ACCESS_TOKEN_COOKIE)
+        .filter(|_| accept_cookie)
+        .map(|cookie| Credentials::Cookie(cookie.value()))
+}
 
 pub async fn auth_guard(
     State(app_state): State<Arc<AppState>>,
     mut request: Request,
     next: Next,
 ) -> Result<Response, AppError> {
-    let token = request
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|header| header.to_str().ok())
-        .and_then(|header| header.strip_prefix("Bearer "))
+    let jar = CookieJar::from_headers(request.headers());
+    let credentials = credentials(request.headers(), &jar, app_state.config.auth_cookies)
         .ok_or(AppError::EmptyToken)?;
 
-    let user = verify_token(app_state, token).await?;
+    let auth_user = match credentials {
+        Credentials::Bearer(token) | Credentials::Cookie(token) => {
+            if let Credentials::Cookie(_) = credentials {
+                AuthCookies::verify_csrf(request.method(), request.headers(), &jar)?;
+            }
+
+            let (user, token_claims) = verify_token(app_state.clone(), token).await?;
+            let impersonator = Impersonation::impersonator(&app_state, &token_claims).await?;
+
+            if let Some(impersonator) = &impersonator {
+                tracing::info!(
+                    impersonator_id = impersonator.id,
+                    user_id = user.id,
+                    method = %request.method(),
+                    uri = %request.uri(),
+                    "impersonated request"
+                );
+            }
+
+            request.extensions_mut().insert(token_claims);
 
-    request.extensions_mut().insert(user);
+            AuthUser {
+                user,
+                api_key: None,
+                impersonator,
+            }
+        }
+        Credentials::ApiKey(api_key) => {
+            let (user, api_key) = ApiKeyStore::authenticate(&app_state, api_key).await?;
+
+            AuthUser {
+                user,
+                api_key: Some(api_key),
+                impersonator: None,
+            }
+        }
+    };
+
+    request.extensions_mut().insert(auth_user.user.clone());
+    request.extensions_mut().insert(auth_user);
 
     let response = next.run(request).await;
 
     Ok(response)
 }
+
+/// Rejects requests authenticated with an API key, for routes that manage the account itself.
+pub async fn session_only(request: Request, next: Next) -> Result<Response, AppError> {
+    let is_api_key = request
+        .extensions()
+        .get::<AuthUser>()
+        .is_none_or(|auth_user| auth_user.api_key.is_some());
+
+    if is_api_key {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(next.run(request).await)
+}
@@ -14,12 +14,29 @@ pub struct Model {
     pub code_name: String,
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::api_key_permission::Entity")]
+    ApiKeyPermission,
+    #[sea_orm(has_many = "super::role_permission::Entity")]
+    RolePermission,
     #[sea_orm(has_many = "super::user_permission::Entity")]
     UserPermission,
 }
 
+impl Related<super::api_key_permission::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiKeyPermission.def()
+    }
+}
+
+impl Related<super::role_permission::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RolePermission.def()
+    }
+}
+
 impl Related<super::user_permission::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::UserPermission.def()
@@ -1,8 +1,18 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
 
+pub use super::api_key::Entity as ApiKey;
+pub use super::api_key_permission::Entity as ApiKeyPermission;
+pub use super::oauth_state::Entity as OauthState;
+pub use super::password_history::Entity as PasswordHistory;
+pub use super::password_reset_token::Entity as PasswordResetToken;
 pub use super::permission::Entity as Permission;
+pub use super::recovery_code::Entity as RecoveryCode;
+pub use super::revoked_token::Entity as RevokedToken;
 pub use super::role::Entity as Role;
+pub use super::role_permission::Entity as RolePermission;
+pub use super::token_family::Entity as TokenFamily;
 pub use super::user::Entity as User;
+pub use super::user_identity::Entity as UserIdentity;
 pub use super::user_permission::Entity as UserPermission;
 pub use super::user_profile::Entity as UserProfile;
 pub use super::user_role::Entity as UserRole;
@@ -15,11 +15,33 @@ pub struct Model {
     pub is_superadmin: bool,
     pub date_created: DateTime,
     pub date_updated: Option<DateTime>,
+    pub email_verified_at: Option<DateTime>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<DateTime>,
+    pub totp_last_used_step: Option<i64>,
+    pub failed_login_attempts: i32,
+    pub last_failed_login_at: Option<DateTime>,
+    pub locked_until: Option<DateTime>,
+    pub token_version: i32,
+    pub pending_email: Option<String>,
+    pub deactivated_at: Option<DateTime>,
 }
 
 #[allow(clippy::enum_variant_names)]
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::api_key::Entity")]
+    ApiKey,
+    #[sea_orm(has_many = "super::password_history::Entity")]
+    PasswordHistory,
+    #[sea_orm(has_many = "super::password_reset_token::Entity")]
+    PasswordResetToken,
+    #[sea_orm(has_many = "super::recovery_code::Entity")]
+    RecoveryCode,
+    #[sea_orm(has_many = "super::token_family::Entity")]
+    TokenFamily,
+    #[sea_orm(has_many = "super::user_identity::Entity")]
+    UserIdentity,
     #[sea_orm(has_many = "super::user_permission::Entity")]
     UserPermission,
     #[sea_orm(has_many = "super::user_profile::Entity")]
@@ -28,6 +50,42 @@ pub enum Relation {
     UserRole,
 }
 
+impl Related<super::api_key::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiKey.def()
+    }
+}
+
+impl Related<super::password_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PasswordHistory.def()
+    }
+}
+
+impl Related<super::password_reset_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PasswordResetToken.def()
+    }
+}
+
+impl Related<super::recovery_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RecoveryCode.def()
+    }
+}
+
+impl Related<super::token_family::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TokenFamily.def()
+    }
+}
+
+impl Related<super::user_identity::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserIdentity.def()
+    }
+}
+
 impl Related<super::user_permission::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::UserPermission.def()
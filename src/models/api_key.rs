@@ -0,0 +1,17 @@
+use sea_orm::{ActiveModelBehavior, Related, RelationDef, RelationTrait};
+
+use super::_entities::{
+    api_key::{ActiveModel, Entity},
+    api_key_permission, permission,
+};
+
+impl Related<permission::Entity> for Entity {
+    fn to() -> RelationDef {
+        api_key_permission::Relation::Permission.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(api_key_permission::Relation::ApiKey.def().rev())
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
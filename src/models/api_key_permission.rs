@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::api_key_permission::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
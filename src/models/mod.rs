@@ -1,7 +1,17 @@
 pub mod _entities;
+pub mod api_key;
+pub mod api_key_permission;
+pub mod oauth_state;
+pub mod password_history;
+pub mod password_reset_token;
 pub mod permission;
+pub mod recovery_code;
+pub mod revoked_token;
 pub mod role;
+pub mod role_permission;
+pub mod token_family;
 pub mod user;
+pub mod user_identity;
 pub mod user_permission;
 pub mod user_profile;
 pub mod user_role;
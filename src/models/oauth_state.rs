@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::oauth_state::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::password_history::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::recovery_code::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,5 +1,18 @@
-use sea_orm::ActiveModelBehavior;
+use sea_orm::{ActiveModelBehavior, Related, RelationDef, RelationTrait};
 
-use super::_entities::role::ActiveModel;
+use super::_entities::{
+    permission,
+    role::{ActiveModel, Entity},
+    role_permission,
+};
+
+impl Related<permission::Entity> for Entity {
+    fn to() -> RelationDef {
+        role_permission::Relation::Permission.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(role_permission::Relation::Role.def().rev())
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
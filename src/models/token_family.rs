@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::token_family::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,4 +1,8 @@
-use sea_orm::{ActiveModelBehavior, ConnectionTrait, DbErr, Related, RelationDef, RelationTrait};
+use sea_orm::{
+    ActiveModelBehavior, ActiveValue, ConnectionTrait, DbErr, Related, RelationDef, RelationTrait,
+};
+
+use crate::auth::identifier::Identifier;
 
 use super::_entities::{
     permission, role,
@@ -26,22 +30,33 @@ impl Related<permission::Entity> for Entity {
 
 #[async_trait::async_trait]
 impl ActiveModelBehavior for ActiveModel {
-    async fn before_save<C>(self, db: &C, insert: bool) -> Result<Self, DbErr>
+    async fn before_save<C>(self, _db: &C, insert: bool) -> Result<Self, DbErr>
     where
         C: ConnectionTrait,
     {
         let now = chrono::Utc::now().naive_utc();
 
-        if insert && self.date_created.is_not_set() {
-            let mut this = self;
-            this.date_created = sea_orm::ActiveValue::Set(now);
-            Ok(this)
-        } else if !insert && self.date_updated.is_unchanged() {
-            let mut this = self;
-            this.date_updated = sea_orm::ActiveValue::Set(Some(now));
-            Ok(this)
-        } else {
-            Ok(self)
+        let mut this = self;
+
+        // identifiers are stored normalized, however they were written
+        if let ActiveValue::Set(username) = &this.username {
+            this.username = ActiveValue::Set(Identifier::normalize_username(username));
+        }
+
+        if let ActiveValue::Set(email) = &this.email {
+            this.email = ActiveValue::Set(Identifier::normalize_email(email));
         }
+
+        if let ActiveValue::Set(Some(pending_email)) = &this.pending_email {
+            this.pending_email = ActiveValue::Set(Some(Identifier::normalize_email(pending_email)));
+        }
+
+        if insert && this.date_created.is_not_set() {
+            this.date_created = ActiveValue::Set(now);
+        } else if !insert && this.date_updated.is_unchanged() {
+            this.date_updated = ActiveValue::Set(Some(now));
+        }
+
+        Ok(this)
     }
 }
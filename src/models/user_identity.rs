@@ -0,0 +1,5 @@
+use sea_orm::ActiveModelBehavior;
+
+use super::_entities::user_identity::ActiveModel;
+
+impl ActiveModelBehavior for ActiveModel {}
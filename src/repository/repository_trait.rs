@@ -1,5 +1,6 @@
 use crate::{error::AppError, form::user_form::CreateUserRequest, models::_entities::user};
 
+#[allow(dead_code)]
 pub trait RepositoryTrait: Send + Sync {
     // async fn find_by_id(&self, id: i32) -> Result<Option<user::Model>, AppError>;
     // async fn find_by_email(&self, email: &str) -> Result<Option<user::Model>, AppError>;
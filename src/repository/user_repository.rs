@@ -4,9 +4,9 @@ use sea_orm::{ColumnTrait, DbErr, EntityTrait, PaginatorTrait, QueryFilter, Quer
 
 use crate::{
     api_response::ResponseMetadata,
+    auth::identifier::Identifier,
     error::AppError,
     models::_entities::{user, user_profile},
-    serializer::UserWithProfileSerializer,
     state::AppState,
 };
 
@@ -22,7 +22,7 @@ pub struct UserRepository {
 impl RepositoryTrait for UserRepository {
     async fn create(
         &self,
-        payload: crate::form::user_form::CreateUserRequest,
+        _payload: crate::form::user_form::CreateUserRequest,
     ) -> Result<user::Model, AppError> {
         todo!()
     }
@@ -95,7 +95,7 @@ impl UserRepository {
     ) -> Result<UserWithProfileModel, AppError> {
         let user_model = user::Entity::find()
             .find_also_related(user_profile::Entity)
-            .filter(user::Column::Username.eq(username))
+            .filter(Identifier::username_eq(username))
             .one(&self.app_state.db)
             .await?
             .ok_or(DbErr::RecordNotFound("User not found".to_string()))?;
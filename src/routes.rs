@@ -1,9 +1,11 @@
 use std::sync::Arc;
 
 use crate::controller::{
-    auth_controller, permission_controller, role_controller, user_controller, user_role_controller,
+    api_key_controller, auth_controller, impersonation_controller, magic_link_controller,
+    me_controller, oauth_controller, permission_controller, role_controller, session_controller,
+    two_factor_controller, user_controller, user_role_controller,
 };
-use crate::{middlewares, state::AppState};
+use crate::{auth::guarded_router::GuardedRouter, middlewares, state::AppState};
 use axum::Router;
 use axum::http::{HeaderValue, Method, StatusCode};
 use axum::middleware;
@@ -11,20 +13,26 @@ use tower_http::{cors::CorsLayer, trace::TraceLayer};
 
 pub async fn create_router(app_state: Arc<AppState>) -> Router {
     Router::new()
-        .nest("/api/users", user_controller::get_routes().await)
-        .nest(
-            "/api/permissions",
-            permission_controller::get_routes().await,
-        )
-        .nest("/api/roles", role_controller::get_routes().await)
-        .nest("/api/user_roles", user_role_controller::get_routes().await)
-        // .nest("/api", controller::auth_controller::get_routes().await)
-        .nest("/api/auth", auth_controller::get_logout_route().await)
+        .merge(api_routes().await)
+        .merge(account_routes().await)
         .route_layer(middleware::from_fn_with_state(
             app_state.clone(),
             middlewares::auth_guard::auth_guard,
         ))
         .nest("/api/auth", auth_controller::get_login_route().await)
+        .nest(
+            "/api/auth/2fa",
+            two_factor_controller::get_public_routes().await,
+        )
+        .nest("/api/auth/oauth", oauth_controller::get_routes().await)
+        .nest(
+            "/api/auth/magic-link",
+            magic_link_controller::get_routes().await,
+        )
+        .nest(
+            "/.well-known",
+            auth_controller::get_well_known_routes().await,
+        )
         .with_state(app_state)
         .fallback(fallback_handler)
         .layer(TraceLayer::new_for_http())
@@ -35,6 +43,34 @@ pub async fn create_router(app_state: Arc<AppState>) -> Router {
         )
 }
 
+/// Routes of the API resources, each of which requires a permission.
+async fn api_routes() -> Router<Arc<AppState>> {
+    GuardedRouter::new()
+        .nest("/api/users", user_controller::get_routes().await)
+        .nest(
+            "/api/permissions",
+            permission_controller::get_routes().await,
+        )
+        .nest("/api/roles", role_controller::get_routes().await)
+        .nest("/api/user_roles", user_role_controller::get_routes().await)
+        .into()
+}
+
+/// Routes managing the account itself, which API keys can't be used for.
+async fn account_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .nest("/api/auth", auth_controller::get_logout_route().await)
+        .nest("/api/auth/me", me_controller::get_routes().await)
+        .nest("/api/auth/2fa", two_factor_controller::get_routes().await)
+        .nest("/api/auth/sessions", session_controller::get_routes().await)
+        .nest("/api/auth/api-keys", api_key_controller::get_routes().await)
+        .nest(
+            "/api/auth/impersonate",
+            impersonation_controller::get_routes().await,
+        )
+        .route_layer(middleware::from_fn(middlewares::auth_guard::session_only))
+}
+
 async fn fallback_handler() -> StatusCode {
     StatusCode::NOT_FOUND
 }
@@ -1,7 +1,8 @@
+use chrono::NaiveDateTime;
 use serde::Serialize;
 
 use crate::{
-    models::_entities::{permission, role, user, user_profile},
+    models::_entities::{api_key, permission, role, token_family, user, user_profile},
     repository::user_repository::UserWithProfileModel,
 };
 
@@ -11,6 +12,10 @@ pub struct UserSerializer {
     pub name: String,
     pub username: String,
     pub email: String,
+    /// Address the user asked to change to, until it is confirmed.
+    pub pending_email: Option<String>,
+    /// Set while the account is deactivated.
+    pub deactivated_at: Option<NaiveDateTime>,
 }
 
 impl From<user::Model> for UserSerializer {
@@ -20,6 +25,8 @@ impl From<user::Model> for UserSerializer {
             name: value.name,
             username: value.username,
             email: value.email,
+            pending_email: value.pending_email,
+            deactivated_at: value.deactivated_at,
         }
     }
 }
@@ -87,6 +94,7 @@ impl From<permission::Model> for PermissionSerializer {
 pub struct RoleSerializer {
     pub id: i32,
     pub name: String,
+    pub parent_id: Option<i32>,
 }
 
 impl From<role::Model> for RoleSerializer {
@@ -94,6 +102,85 @@ impl From<role::Model> for RoleSerializer {
         Self {
             id: value.id,
             name: value.name,
+            parent_id: value.parent_id,
         }
     }
 }
+
+#[derive(Debug, Serialize)]
+pub struct RolePermissionSerializer {
+    #[serde(flatten)]
+    pub permission: PermissionSerializer,
+    /// Role the permission is inherited from, `None` for the role's own permissions.
+    pub inherited_from: Option<RoleSerializer>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionSerializer {
+    pub id: i32,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub date_created: NaiveDateTime,
+    pub last_refreshed_at: Option<NaiveDateTime>,
+    /// Whether the request was made with a token of this session.
+    pub current: bool,
+}
+
+impl SessionSerializer {
+    pub fn new(value: token_family::Model, current_session_id: Option<i32>) -> Self {
+        Self {
+            current: current_session_id == Some(value.id),
+            id: value.id,
+            user_agent: value.user_agent,
+            ip_address: value.ip_address,
+            date_created: value.date_created,
+            last_refreshed_at: value.last_refreshed_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeySerializer {
+    pub id: i32,
+    pub name: String,
+    pub key_prefix: String,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub date_created: NaiveDateTime,
+    pub permissions: Vec<String>,
+}
+
+impl ApiKeySerializer {
+    pub fn new(value: api_key::Model, permissions: Vec<permission::Model>) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            key_prefix: value.key_prefix,
+            expires_at: value.expires_at,
+            last_used_at: value.last_used_at,
+            revoked_at: value.revoked_at,
+            date_created: value.date_created,
+            permissions: permissions
+                .into_iter()
+                .map(|permission| permission.code_name)
+                .collect(),
+        }
+    }
+}
+
+/// Returned once, when the key is created, as only its digest is stored.
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKeySerializer {
+    #[serde(flatten)]
+    pub api_key: ApiKeySerializer,
+    pub key: String,
+}
+
+/// What the signed-in user may do, so clients can hide controls they can't use.
+#[derive(Debug, Serialize)]
+pub struct EffectivePermissionsSerializer {
+    pub is_superadmin: bool,
+    pub permissions: Vec<String>,
+    pub roles: Vec<String>,
+}
@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 
 use crate::{
-    api_response::ResponseMetadata, error::AppError, form::user_form::CreateUserRequest,
-    models::_entities::user, repository::user_repository::UserWithProfileModel,
+    api_response::ResponseMetadata, error::AppError,
+    repository::user_repository::UserWithProfileModel,
 };
 
 pub trait ServiceTrait: Send + Sync {
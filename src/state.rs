@@ -1,8 +1,47 @@
-use crate::configgg::AppConfig;
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    auth::{
+        jwt_keys::JwtKeys, login_throttle::LoginThrottle, oidc::OidcProviders,
+        password::PasswordHasher, password_policy::PasswordPolicy,
+        permission_cache::PermissionCache,
+    },
+    configgg::AppConfig,
+};
 use sea_orm::DatabaseConnection;
 
 #[derive(Clone, Debug)]
 pub struct AppState {
     pub db: DatabaseConnection,
     pub config: AppConfig,
+    pub jwt_keys: Arc<JwtKeys>,
+    pub login_throttle: Arc<LoginThrottle>,
+    pub oidc_providers: Arc<OidcProviders>,
+    pub password_policy: Arc<PasswordPolicy>,
+    pub permission_cache: Arc<PermissionCache>,
+}
+
+impl AppState {
+    pub fn new(db: DatabaseConnection, config: AppConfig) -> Result<Self, String> {
+        let jwt_keys = Arc::new(JwtKeys::from_config(&config)?);
+        let oidc_providers = Arc::new(OidcProviders::from_config(&config)?);
+        let password_policy = Arc::new(PasswordPolicy::from_config(&config)?);
+        let permission_cache = Arc::new(PermissionCache::new(Duration::from_secs(
+            config.permission_cache_ttl_seconds,
+        )));
+
+        PasswordHasher::new(&config)
+            .and_then(|password_hasher| password_hasher.init_dummy_hash())
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            db,
+            config,
+            jwt_keys,
+            login_throttle: Arc::new(LoginThrottle::default()),
+            oidc_providers,
+            password_policy,
+            permission_cache,
+        })
+    }
 }
@@ -0,0 +1,223 @@
+//! Fixtures shared by the request tests. The test database outlives a run, so fixtures are
+//! looked up by name and only created when missing.
+
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Method, Request, StatusCode, header},
+    response::Response,
+};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait as _, ActiveValue::NotSet, ColumnTrait as _, EntityTrait as _,
+    QueryFilter as _, Set, TryIntoModel as _,
+};
+use serde_json::Value;
+use tower::ServiceExt as _;
+
+use crate::{
+    auth::{
+        password::PasswordHasher,
+        token_store::{SessionDevice, TokenStore},
+    },
+    configgg::AppConfig,
+    models::_entities::{permission, role, user},
+    state::AppState,
+    utils::connect_to_database,
+};
+
+pub async fn test_state() -> Arc<AppState> {
+    test_state_with(|_| {}).await
+}
+
+/// App state for a test that needs settings other than the ones from the environment.
+pub async fn test_state_with(configure: impl FnOnce(&mut AppConfig)) -> Arc<AppState> {
+    let mut app_config = AppConfig::from_env().unwrap();
+    configure(&mut app_config);
+
+    let db = connect_to_database(&app_config.database_url).await.unwrap();
+
+    Arc::new(AppState::new(db, app_config).unwrap())
+}
+
+pub async fn find_or_create_user(
+    app_state: &AppState,
+    username: &str,
+    is_superadmin: bool,
+) -> user::Model {
+    let existing = user::Entity::find()
+        .filter(user::Column::Username.eq(username))
+        .one(&app_state.db)
+        .await
+        .unwrap();
+
+    match existing {
+        Some(user) => user,
+        None => user::ActiveModel {
+            name: Set(username.to_string()),
+            username: Set(username.to_string()),
+            email: Set(format!("{username}@example.com")),
+            password: Set(String::new()),
+            is_superadmin: Set(is_superadmin),
+            date_created: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&app_state.db)
+        .await
+        .unwrap(),
+    }
+}
+
+/// Returns the user with the given username, reset to unverified.
+pub async fn unverified_user(app_state: &AppState, username: &str) -> user::Model {
+    let mut user: user::ActiveModel = find_or_create_user(app_state, username, false).await.into();
+    user.email_verified_at = Set(None);
+
+    user.save(&app_state.db)
+        .await
+        .unwrap()
+        .try_into_model()
+        .unwrap()
+}
+
+/// Returns the verified and active user with the given username and password, without failed
+/// login attempts or 2FA.
+pub async fn user_with_password(
+    app_state: &AppState,
+    username: &str,
+    password: &str,
+) -> user::Model {
+    let mut user: user::ActiveModel = find_or_create_user(app_state, username, false).await.into();
+    user.password = Set(PasswordHasher::new(&app_state.config)
+        .unwrap()
+        .hash(password)
+        .await
+        .unwrap());
+    user.email_verified_at = Set(Some(Utc::now().naive_utc()));
+    user.totp_secret = Set(None);
+    user.totp_enabled_at = Set(None);
+    user.totp_last_used_step = Set(None);
+    user.failed_login_attempts = Set(0);
+    user.last_failed_login_at = Set(None);
+    user.locked_until = Set(None);
+    user.deactivated_at = Set(None);
+
+    user.update(&app_state.db).await.unwrap()
+}
+
+/// Finds the role, or creates it, without a parent either way.
+pub async fn find_or_create_role(app_state: &AppState, name: &str) -> role::Model {
+    let existing = role::Entity::find()
+        .filter(role::Column::Name.eq(name))
+        .one(&app_state.db)
+        .await
+        .unwrap();
+
+    match existing {
+        Some(role) => role::ActiveModel {
+            parent_id: Set(None),
+            ..role.into()
+        }
+        .update(&app_state.db)
+        .await
+        .unwrap(),
+        None => role::ActiveModel {
+            id: NotSet,
+            name: Set(name.to_string()),
+            parent_id: Set(None),
+        }
+        .insert(&app_state.db)
+        .await
+        .unwrap(),
+    }
+}
+
+pub async fn find_or_create_permission(app_state: &AppState, code_name: &str) -> permission::Model {
+    let existing = permission::Entity::find()
+        .filter(permission::Column::CodeName.eq(code_name))
+        .one(&app_state.db)
+        .await
+        .unwrap();
+
+    match existing {
+        Some(permission) => permission,
+        None => permission::ActiveModel {
+            id: NotSet,
+            name: Set(code_name.to_string()),
+            code_name: Set(code_name.to_string()),
+        }
+        .insert(&app_state.db)
+        .await
+        .unwrap(),
+    }
+}
+
+/// Signs the user in, with the token version currently stored for them.
+pub async fn access_token(app_state: &Arc<AppState>, user_id: i32) -> String {
+    let user = user::Entity::find_by_id(user_id)
+        .one(&app_state.db)
+        .await
+        .unwrap()
+        .unwrap();
+
+    TokenStore::issue_token_pair(app_state, &user, SessionDevice::default())
+        .await
+        .unwrap()
+        .access_token
+}
+
+/// A request with a JSON body, or without a body when it is `Value::Null`.
+pub fn json_request(
+    method: Method,
+    uri: &str,
+    access_token: Option<&str>,
+    body: &Value,
+) -> Request<Body> {
+    let mut builder = Request::builder().method(method).uri(uri);
+
+    if let Some(access_token) = access_token {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {access_token}"));
+    }
+
+    let body = match body {
+        Value::Null => Body::empty(),
+        body => {
+            builder = builder.header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref());
+            Body::from(body.to_string())
+        }
+    };
+
+    builder.body(body).unwrap()
+}
+
+pub async fn post_json(app: &Router, uri: &str, body: Value) -> Response {
+    app.clone()
+        .oneshot(json_request(Method::POST, uri, None, &body))
+        .await
+        .unwrap()
+}
+
+/// Sends the request, returning the status and the JSON body, or `Value::Null` without one.
+pub async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+
+    let status = response.status();
+    let body_bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    (
+        status,
+        serde_json::from_slice(&body_bytes).unwrap_or(Value::Null),
+    )
+}
+
+pub async fn request(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    access_token: Option<&str>,
+    body: Value,
+) -> (StatusCode, Value) {
+    send(app, json_request(method, uri, access_token, &body)).await
+}
@@ -1,61 +1,23 @@
 use std::sync::Arc;
 
-use hmac::{self, Hmac, Mac};
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use sea_orm::{ColumnTrait, DbErr};
-use sea_orm::{EntityTrait, QueryFilter};
-use sha2::Sha256;
-
 use crate::AppState;
-use crate::{auth::jwt::TokenClaims, error::AppError, models::_entities::user};
-
-pub fn hash(text: &str) -> String {
-    let mut mac: Hmac<Sha256> =
-        Hmac::new_from_slice(b"secret_key").expect("HMAC can take key of any size");
-
-    mac.update(text.as_bytes());
-
-    let result = mac.finalize();
-    let code_bytes = result.into_bytes();
-
-    hex::encode(code_bytes)
-}
+use crate::auth::email_verification::EmailVerification;
+use crate::auth::jwt::{TokenClaims, TokenType};
+use crate::auth::token_store::TokenStore;
+use crate::{error::AppError, models::_entities::user};
 
-pub fn verify_password(hex_code: &str, to_verify: &str) -> Result<bool, AppError> {
-    let mut mac: Hmac<Sha256> =
-        Hmac::new_from_slice(b"secret_key").expect("HMAC can take key of any size");
-
-    mac.update(to_verify.as_bytes());
-
-    let result = mac.finalize();
-
-    let code_byte = hex::decode(hex_code).map_err(|err| AppError::GenericError(err.to_string()))?;
-
-    Ok(code_byte[..] == result.into_bytes()[..])
-}
+pub async fn verify_token(
+    app_state: Arc<AppState>,
+    token: &str,
+) -> Result<(user::Model, TokenClaims), AppError> {
+    // refresh tokens are only accepted by the refresh endpoint
+    let token_claims = app_state.jwt_keys.decode(token, TokenType::Access)?;
 
-pub async fn verify_token(app_state: Arc<AppState>, token: &str) -> Result<user::Model, AppError> {
-    let token_claim = decode::<TokenClaims>(
-        token,
-        &DecodingKey::from_secret(app_state.config.jwt_secret.as_ref()),
-        &Validation::default(),
-    )
-    .map_err(|e| {
-        tracing::error!("{:#?}", e);
-        match e.kind() {
-            jsonwebtoken::errors::ErrorKind::InvalidToken => AppError::InvalidToken,
-            jsonwebtoken::errors::ErrorKind::ExpiredSignature => AppError::TokenExpired,
-            _ => AppError::Unauthorized,
-        }
-    })?;
+    let user = TokenStore::authenticate(&app_state.db, &token_claims).await?;
 
-    let user = user::Entity::find()
-        .filter(user::Column::Email.eq(token_claim.claims.sub))
-        .one(&app_state.db)
-        .await?
-        .ok_or(DbErr::RecordNotFound("User not found.".to_string()))?;
+    EmailVerification::ensure_verified(&app_state.config, &user)?;
 
-    Ok(user)
+    Ok((user, token_claims))
 }
 
 pub async fn connect_to_database(